@@ -1,791 +1,2674 @@
 #![allow(clippy::suspicious_else_formatting)]
 #![allow(clippy::toplevel_ref_arg)]
-use crate::Error;
-use casbin::{error::AdapterError, Error as CasbinError, Filter, Result};
-use sqlx::error::Error as SqlxError;
-
-use crate::models::{CasbinRule, NewCasbinRule};
-
+use crate::{error::ResultExt, Error};
+use casbin::{Error as CasbinError, Filter, Result};
+use futures_util::{future::try_join3, Stream, TryStreamExt};
+use serde_json::{json, Value};
+use sqlx::{
+    error::Error as SqlxError,
+    pool::PoolConnection,
+    query::{Query, QueryAs, QueryScalar},
+    types::Json,
+    Connection, Database, QueryBuilder, Transaction,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+
+use crate::{
+    id_type::IdType,
+    index::{IndexSpec, TableIndex},
+    isolation::IsolationLevel,
+    load_order::LoadOrder,
+    metrics_hook::Operation,
+    migrations::{bigint_id_column, create_table, timestamp_columns, Backend, MIGRATIONS},
+    models::{CasbinRule, NewCasbinRule},
+    queries::{quote, rule_hash, Queries, MIGRATIONS_TABLE},
+    schema_report::{IncompatibleColumn, SchemaReport},
+    section::Section,
+};
+
+#[cfg(all(feature = "compile-checked", feature = "postgres"))]
+use crate::checked;
 #[cfg(feature = "postgres")]
-use sqlx::postgres::PgQueryResult;
+use crate::index::Column;
+#[cfg(feature = "postgres")]
+use sqlx::postgres::{PgConnection, PgQueryResult};
 
 #[cfg(feature = "mysql")]
-use sqlx::mysql::MySqlQueryResult;
+use crate::migrations::PTYPE_LENGTH;
+#[cfg(feature = "mysql")]
+use sqlx::mysql::{MySqlConnection, MySqlDatabaseError, MySqlQueryResult};
 
 #[cfg(feature = "sqlite")]
-use sqlx::sqlite::SqliteQueryResult;
+use crate::queries::identifier;
+#[cfg(feature = "sqlite")]
+use sqlx::sqlite::{SqliteConnection, SqliteQueryResult};
 
 #[cfg(feature = "postgres")]
 pub type ConnectionPool = sqlx::PgPool;
+#[cfg(feature = "postgres")]
+pub(crate) type ConnectOptions = sqlx::postgres::PgConnectOptions;
 
 #[cfg(feature = "mysql")]
 pub type ConnectionPool = sqlx::MySqlPool;
+#[cfg(feature = "mysql")]
+pub(crate) type ConnectOptions = sqlx::mysql::MySqlConnectOptions;
 
 #[cfg(feature = "sqlite")]
 pub type ConnectionPool = sqlx::SqlitePool;
+#[cfg(feature = "sqlite")]
+pub(crate) type ConnectOptions = sqlx::sqlite::SqliteConnectOptions;
 
 #[cfg(feature = "postgres")]
-pub async fn new_with_table_name(conn: &ConnectionPool, table_name: &str) -> Result<PgQueryResult> {
-    sqlx::query(&format!(
-        "CREATE TABLE IF NOT EXISTS {} (
-                    id SERIAL PRIMARY KEY,
-                    ptype VARCHAR NOT NULL,
-                    v0 VARCHAR NOT NULL,
-                    v1 VARCHAR NOT NULL,
-                    v2 VARCHAR NOT NULL,
-                    v3 VARCHAR NOT NULL,
-                    v4 VARCHAR NOT NULL,
-                    v5 VARCHAR NOT NULL,
-                    CONSTRAINT unique_key_sqlx_adapter_{} UNIQUE(ptype, v0, v1, v2, v3, v4, v5)
-                    );
-        ",
-        table_name, table_name
+pub(crate) type Db = sqlx::Postgres;
+
+#[cfg(feature = "mysql")]
+pub(crate) type Db = sqlx::MySql;
+
+#[cfg(feature = "sqlite")]
+pub(crate) type Db = sqlx::Sqlite;
+
+#[cfg(feature = "postgres")]
+type DbConnection = PgConnection;
+
+#[cfg(feature = "mysql")]
+type DbConnection = MySqlConnection;
+
+#[cfg(feature = "sqlite")]
+type DbConnection = SqliteConnection;
+
+#[cfg(feature = "postgres")]
+type DbQueryResult = PgQueryResult;
+
+#[cfg(feature = "mysql")]
+type DbQueryResult = MySqlQueryResult;
+
+#[cfg(feature = "sqlite")]
+type DbQueryResult = SqliteQueryResult;
+
+// Statements scoped to a tenant take it as their first parameter, ahead of
+// their own.
+pub(crate) trait BindTenant<'q> {
+    fn bind_tenant(self, queries: &'q Queries) -> Self;
+}
+
+impl<'q> BindTenant<'q> for Query<'q, Db, <Db as Database>::Arguments<'q>> {
+    fn bind_tenant(self, queries: &'q Queries) -> Self {
+        match queries.tenant() {
+            Some(tenant) => self.bind(tenant),
+            None => self,
+        }
+    }
+}
+
+impl<'q, O> BindTenant<'q> for QueryAs<'q, Db, O, <Db as Database>::Arguments<'q>> {
+    fn bind_tenant(self, queries: &'q Queries) -> Self {
+        match queries.tenant() {
+            Some(tenant) => self.bind(tenant),
+            None => self,
+        }
+    }
+}
+
+impl<'q, O> BindTenant<'q> for QueryScalar<'q, Db, O, <Db as Database>::Arguments<'q>> {
+    fn bind_tenant(self, queries: &'q Queries) -> Self {
+        match queries.tenant() {
+            Some(tenant) => self.bind(tenant),
+            None => self,
+        }
+    }
+}
+
+// Statements that stamp rules with the acting principal take it after their
+// own parameters.
+pub(crate) trait BindActor<'q> {
+    fn bind_actor(self, queries: &'q Queries) -> Self;
+}
+
+impl<'q> BindActor<'q> for Query<'q, Db, <Db as Database>::Arguments<'q>> {
+    fn bind_actor(self, queries: &'q Queries) -> Self {
+        match queries.actor() {
+            Some(actor) => self.bind(actor),
+            None => self,
+        }
+    }
+}
+
+// With `rule_hash`, statements that insert or look up a single rule take its
+// hash right after the rule's values.
+pub(crate) trait BindRuleHash<'q>: Sized {
+    fn bind_hash(self, hash: Option<i64>) -> Self;
+
+    fn bind_rule_hash(self, queries: &Queries, pt: &str, rule: [&str; 6]) -> Self {
+        self.bind_hash(Some(rule_hash(pt, rule)).filter(|_| queries.options.rule_hash))
+    }
+
+    fn bind_lookup_hash(self, queries: &Queries, pt: &str, rule: [&str; 6]) -> Self {
+        self.bind_hash(Some(rule_hash(pt, rule)).filter(|_| queries.options.hash_lookups()))
+    }
+}
+
+impl<'q> BindRuleHash<'q> for Query<'q, Db, <Db as Database>::Arguments<'q>> {
+    fn bind_hash(self, hash: Option<i64>) -> Self {
+        match hash {
+            Some(hash) => self.bind(hash),
+            None => self,
+        }
+    }
+}
+
+impl<'q, O> BindRuleHash<'q> for QueryScalar<'q, Db, O, <Db as Database>::Arguments<'q>> {
+    fn bind_hash(self, hash: Option<i64>) -> Self {
+        match hash {
+            Some(hash) => self.bind(hash),
+            None => self,
+        }
+    }
+}
+
+// Every rule binds up to ten parameters, its own seven, the tenant, the hash
+// and the actor. Postgres and MySQL cap a statement at 65535 of them, SQLite
+// at SQLITE_MAX_VARIABLE_NUMBER (999 by default).
+// Statements sized by their chunk are not persisted, since every distinct
+// length would otherwise take its own slot in the per-connection cache.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+pub(crate) const MAX_RULES_PER_STATEMENT: usize = 65535 / 10;
+
+#[cfg(feature = "sqlite")]
+pub(crate) const MAX_RULES_PER_STATEMENT: usize = 999 / 10;
+
+// Identity columns need Postgres 10, older servers keep the legacy SERIAL.
+#[cfg(feature = "postgres")]
+const PG_IDENTITY_MIN_VERSION: i32 = 100000;
+
+#[cfg(feature = "postgres")]
+async fn server_version(conn: &ConnectionPool) -> Result<i32> {
+    sqlx::query_scalar("SELECT current_setting('server_version_num')::int")
+        .fetch_one(conn)
+        .await
+        .adapt()
+}
+
+#[cfg(feature = "postgres")]
+pub async fn new_with_table_name(
+    conn: &ConnectionPool,
+    queries: &Queries,
+) -> Result<PgQueryResult> {
+    let id_column = if queries.options.id_type == IdType::Uuid {
+        "id UUID PRIMARY KEY DEFAULT gen_random_uuid()"
+    } else if server_version(conn).await? >= PG_IDENTITY_MIN_VERSION {
+        bigint_id_column(Backend::CURRENT)
+    } else {
+        "id BIGSERIAL PRIMARY KEY"
+    };
+
+    sqlx::query(&create_table(
+        Backend::CURRENT,
+        &queries.table,
+        &queries.table_name,
+        id_column,
     ))
     .execute(conn)
     .await
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .adapt()
+}
+
+#[cfg(feature = "postgres")]
+pub async fn migrate_to_identity(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut transaction = conn.begin().await.adapt()?;
+
+    let identity: String = sqlx::query_scalar(
+        "SELECT attidentity::text FROM pg_attribute WHERE attrelid = $1::regclass AND attname = 'id'",
+    )
+    .bind(&*queries.table)
+    .fetch_one(&mut *transaction)
+    .await
+    .adapt()?;
+
+    if !identity.is_empty() {
+        return Ok(());
+    }
+
+    let sequence: Option<String> = sqlx::query_scalar("SELECT pg_get_serial_sequence($1, 'id')")
+        .bind(&*queries.table)
+        .fetch_one(&mut *transaction)
+        .await
+        .adapt()?;
+
+    let next_id: i64 = sqlx::query_scalar(&format!(
+        "SELECT COALESCE(MAX(id), 0)::BIGINT + 1 FROM {}",
+        queries.table
+    ))
+    .fetch_one(&mut *transaction)
+    .await
+    .adapt()?;
+
+    let mut statements = vec![format!(
+        "ALTER TABLE {} ALTER COLUMN id DROP DEFAULT",
+        queries.table
+    )];
+    if let Some(sequence) = sequence {
+        statements.push(format!("DROP SEQUENCE {}", sequence));
+    }
+    statements.push(format!(
+        "ALTER TABLE {} ALTER COLUMN id ADD GENERATED ALWAYS AS IDENTITY (START WITH {})",
+        queries.table, next_id
+    ));
+
+    for statement in statements {
+        sqlx::query(&statement)
+            .execute(&mut *transaction)
+            .await
+            .adapt()?;
+    }
+
+    transaction.commit().await.adapt()
+}
+
+// The extension is only created when missing, which takes CREATE on the
+// database from Postgres 13 on, where citext is trusted, and a superuser
+// before. Columns already of the type are left as they are.
+#[cfg(feature = "postgres")]
+pub async fn make_case_insensitive(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    columns: &[Column],
+) -> Result<()> {
+    let mut transaction = conn.begin().await.adapt()?;
+
+    let installed: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM pg_extension WHERE extname = 'citext')")
+            .fetch_one(&mut *transaction)
+            .await
+            .adapt()?;
+    if !installed {
+        sqlx::query("CREATE EXTENSION IF NOT EXISTS citext")
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| CasbinError::from(Error::CitextUnavailable(err)))?;
+    }
+
+    let converted: Vec<String> = sqlx::query_scalar(
+        "SELECT attname::text FROM pg_attribute
+            WHERE attrelid = $1::regclass AND atttypid = 'citext'::regtype AND NOT attisdropped",
+    )
+    .bind(&*queries.table)
+    .fetch_all(&mut *transaction)
+    .await
+    .adapt()?;
+
+    for column in columns {
+        if converted.iter().any(|name| name == column.as_str()) {
+            continue;
+        }
+        // Rebuilds the unique index, which fails on rules that only differ
+        // in case.
+        sqlx::query(&format!(
+            "ALTER TABLE {} ALTER COLUMN {} TYPE CITEXT",
+            queries.table,
+            column.as_str()
+        ))
+        .execute(&mut *transaction)
+        .await
+        .adapt()?;
+    }
+
+    transaction.commit().await.adapt()
+}
+
+// Widens the id of tables created with a 32-bit key, and the sequence behind
+// it. Sequences only have a type of their own from Postgres 10 on.
+#[cfg(feature = "postgres")]
+pub async fn migrate_to_bigint_ids(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let server_version = server_version(conn).await?;
+
+    let mut transaction = conn.begin().await.adapt()?;
+
+    sqlx::query(&format!(
+        "ALTER TABLE {} ALTER COLUMN id TYPE BIGINT",
+        queries.table
+    ))
+    .execute(&mut *transaction)
+    .await
+    .adapt()?;
+
+    let sequence: Option<String> = sqlx::query_scalar("SELECT pg_get_serial_sequence($1, 'id')")
+        .bind(&*queries.table)
+        .fetch_one(&mut *transaction)
+        .await
+        .adapt()?;
+
+    if let Some(sequence) = sequence.filter(|_| server_version >= PG_IDENTITY_MIN_VERSION) {
+        sqlx::query(&format!("ALTER SEQUENCE {} AS BIGINT", sequence))
+            .execute(&mut *transaction)
+            .await
+            .adapt()?;
+    }
+
+    transaction.commit().await.adapt()
 }
 
+// A version 4 UUID in its text form, SQLite has no function for one.
+#[cfg(feature = "sqlite")]
+const SQLITE_UUID_COLUMN: &str = "id TEXT NOT NULL PRIMARY KEY DEFAULT (lower(
+                        hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+                        substr(hex(randomblob(2)), 2) || '-' ||
+                        substr('89ab', 1 + (abs(random()) % 4), 1) ||
+                        substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6))
+                    ))";
+
 #[cfg(feature = "sqlite")]
 pub async fn new_with_table_name(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
 ) -> Result<SqliteQueryResult> {
-    sqlx::query(&format!(
-        "CREATE TABLE IF NOT EXISTS {} (
-                    id SERIAL PRIMARY KEY,
-                    ptype VARCHAR NOT NULL,
-                    v0 VARCHAR NOT NULL,
-                    v1 VARCHAR NOT NULL,
-                    v2 VARCHAR NOT NULL,
-                    v3 VARCHAR NOT NULL,
-                    v4 VARCHAR NOT NULL,
-                    v5 VARCHAR NOT NULL,
-                    CONSTRAINT unique_key_sqlx_adapter_{} UNIQUE(ptype, v0, v1, v2, v3, v4, v5)
-                    );
-        ",
-        table_name, table_name
+    let id_column = match queries.options.id_type {
+        IdType::BigInt => bigint_id_column(Backend::CURRENT),
+        IdType::Uuid => SQLITE_UUID_COLUMN,
+    };
+
+    sqlx::query(&create_table(
+        Backend::CURRENT,
+        &queries.table,
+        &queries.table_name,
+        id_column,
     ))
     .execute(conn)
     .await
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .adapt()
 }
 
 #[cfg(feature = "mysql")]
 pub async fn new_with_table_name(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
 ) -> Result<MySqlQueryResult> {
-    sqlx::query(&format!(
-        "CREATE TABLE IF NOT EXISTS {} (
-                    id INT NOT NULL AUTO_INCREMENT,
-                    ptype VARCHAR(12) NOT NULL,
-                    v0 VARCHAR(128) NOT NULL,
-                    v1 VARCHAR(128) NOT NULL,
-                    v2 VARCHAR(128) NOT NULL,
-                    v3 VARCHAR(128) NOT NULL,
-                    v4 VARCHAR(128) NOT NULL,
-                    v5 VARCHAR(128) NOT NULL,
-                    PRIMARY KEY(id),
-                    CONSTRAINT unique_key_sqlx_adapter_{} UNIQUE(ptype, v0, v1, v2, v3, v4, v5)
-                ) ENGINE=InnoDB DEFAULT CHARSET=utf8;",
-        table_name, table_name
+    let id_column = match queries.options.id_type {
+        IdType::BigInt => bigint_id_column(Backend::CURRENT),
+        IdType::Uuid => "id CHAR(36) NOT NULL DEFAULT (UUID())",
+    };
+
+    sqlx::query(&create_table(
+        Backend::CURRENT,
+        &queries.table,
+        &queries.table_name,
+        id_column,
     ))
     .execute(conn)
     .await
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .adapt()
 }
 
-#[allow(dead_code)]
+// Runs a caller's own DDL in place of `new_with_table_name`. It may hold
+// several statements, so it is sent as is rather than prepared.
+pub(crate) async fn execute_ddl(conn: &ConnectionPool, ddl: &str) -> Result<()> {
+    sqlx::raw_sql(ddl).execute(conn).await.map(|_| ()).adapt()
+}
+
+// Every column of the table with its type and whether it allows NULL, none
+// when the table does not exist.
 #[cfg(feature = "postgres")]
-pub async fn new(conn: &ConnectionPool) -> Result<PgQueryResult> {
-    new_with_table_name(conn, "casbin_rule").await
+async fn table_columns(conn: &ConnectionPool, queries: &Queries) -> Result<Vec<TableColumn>> {
+    sqlx::query_as(
+        "SELECT attname::text, format_type(atttypid, NULL), NOT attnotnull FROM pg_attribute
+            WHERE attrelid = to_regclass($1) AND attnum > 0 AND NOT attisdropped
+            ORDER BY attnum",
+    )
+    .bind(&*queries.table)
+    .fetch_all(conn)
+    .await
+    .adapt()
+}
+
+#[cfg(feature = "mysql")]
+async fn table_columns(conn: &ConnectionPool, queries: &Queries) -> Result<Vec<TableColumn>> {
+    sqlx::query_as::<_, (String, String, String)>(
+        "SELECT CAST(COLUMN_NAME AS CHAR), CAST(DATA_TYPE AS CHAR), CAST(IS_NULLABLE AS CHAR)
+            FROM information_schema.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+            ORDER BY ORDINAL_POSITION",
+    )
+    .bind(&*queries.table_name)
+    .fetch_all(conn)
+    .await
+    .map(|columns| {
+        columns
+            .into_iter()
+            .map(|(name, data_type, nullable)| (name, data_type, nullable == "YES"))
+            .collect()
+    })
+    .adapt()
 }
 
 #[cfg(feature = "sqlite")]
-pub async fn new(conn: &ConnectionPool) -> Result<SqliteQueryResult> {
-    new_with_table_name(conn, "casbin_rule").await
+async fn table_columns(conn: &ConnectionPool, queries: &Queries) -> Result<Vec<TableColumn>> {
+    sqlx::query_as(
+        "SELECT name, lower(type), NOT \"notnull\" FROM pragma_table_info(?1) ORDER BY cid",
+    )
+    .bind(&*queries.table_name)
+    .fetch_all(conn)
+    .await
+    .adapt()
 }
 
-#[cfg(feature = "mysql")]
-pub async fn new(conn: &ConnectionPool) -> Result<MySqlQueryResult> {
-    new_with_table_name(conn, "casbin_rule").await
+#[cfg(feature = "postgres")]
+fn is_text_type(data_type: &str) -> bool {
+    matches!(data_type, "character varying" | "text" | "citext")
 }
 
 #[cfg(feature = "postgres")]
-pub async fn remove_policy(
+fn is_integer_type(data_type: &str) -> bool {
+    matches!(data_type, "smallint" | "integer" | "bigint")
+}
+
+#[cfg(feature = "mysql")]
+fn is_text_type(data_type: &str) -> bool {
+    matches!(
+        data_type,
+        "varchar" | "char" | "tinytext" | "text" | "mediumtext" | "longtext"
+    )
+}
+
+#[cfg(feature = "mysql")]
+fn is_integer_type(data_type: &str) -> bool {
+    matches!(
+        data_type,
+        "tinyint" | "smallint" | "mediumint" | "int" | "bigint"
+    )
+}
+
+// SQLite goes by the type affinity the declared type names.
+#[cfg(feature = "sqlite")]
+fn is_text_type(data_type: &str) -> bool {
+    !data_type.contains("int")
+        && ["char", "clob", "text"]
+            .iter()
+            .any(|name| data_type.contains(name))
+}
+
+#[cfg(feature = "sqlite")]
+fn is_integer_type(data_type: &str) -> bool {
+    data_type.contains("int")
+}
+
+const RULE_COLUMNS: [&str; 7] = ["ptype", "v0", "v1", "v2", "v3", "v4", "v5"];
+
+// Columns added by the adapter's own migrations and opt-in features.
+const ADAPTER_COLUMNS: [&str; 11] = [
+    "tenant_id",
+    "created_at",
+    "updated_at",
+    "deleted_at",
+    "not_deleted",
+    "metadata",
+    "enabled",
+    "expires_at",
+    "modified_by",
+    "description",
+    "rule_hash",
+];
+
+type TableColumn = (String, String, bool);
+
+pub(crate) async fn schema_report(
     conn: &ConnectionPool,
-    table_name: &str,
-    pt: &str,
-    rule: Vec<String>,
-) -> Result<bool> {
-    let rule = normalize_casbin_rule(rule);
+    queries: &Queries,
+) -> Result<SchemaReport> {
+    let columns = table_columns(conn, queries).await?;
+    let mut report = SchemaReport::default();
+
+    match columns.iter().find(|(name, ..)| name == "id") {
+        Some((_, data_type, _)) => {
+            let data_type = data_type.to_lowercase();
+            if is_integer_type(&data_type) {
+                report.id_type = Some(IdType::BigInt);
+            } else if is_text_type(&data_type)
+                || (cfg!(feature = "postgres") && data_type == "uuid")
+            {
+                report.id_type = Some(IdType::Uuid);
+            } else {
+                report.incompatible.push(IncompatibleColumn {
+                    column: "id",
+                    data_type,
+                });
+            }
+        }
+        None => report.missing.push("id"),
+    }
+
+    for column in RULE_COLUMNS.iter() {
+        match columns.iter().find(|(name, ..)| name == column) {
+            Some((_, data_type, nullable)) => {
+                let data_type = data_type.to_lowercase();
+                if !is_text_type(&data_type) {
+                    report
+                        .incompatible
+                        .push(IncompatibleColumn { column, data_type });
+                }
+                if *nullable {
+                    report.nullable.push(column);
+                }
+            }
+            None => report.missing.push(column),
+        }
+    }
+
+    report.extra = columns
+        .into_iter()
+        .map(|(name, ..)| name)
+        .filter(|name| {
+            name != "id"
+                && !RULE_COLUMNS.contains(&name.as_str())
+                && !ADAPTER_COLUMNS.contains(&name.as_str())
+        })
+        .collect();
+    Ok(report)
+}
+
+async fn apply_migration(conn: &ConnectionPool, queries: &Queries, version: i64) -> Result<()> {
+    match version {
+        1 => new_with_table_name(conn, queries).await.map(|_| ()),
+        #[cfg(any(feature = "postgres", feature = "mysql"))]
+        2 if queries.options.id_type == IdType::BigInt => {
+            migrate_to_bigint_ids(conn, queries).await
+        }
+        #[cfg(feature = "postgres")]
+        3 if queries.options.id_type == IdType::BigInt => {
+            if server_version(conn).await? >= PG_IDENTITY_MIN_VERSION {
+                migrate_to_identity(conn, queries).await
+            } else {
+                Ok(())
+            }
+        }
+        #[cfg(feature = "mysql")]
+        4 => {
+            alter_collation(conn, queries).await?;
+            widen_ptype_column(conn, queries).await
+        }
+        5 => add_timestamp_columns(conn, queries).await,
+        _ => Ok(()),
+    }
+}
+
+// Drops the table along with its revision table and outbox, and forgets its
+// migrations so that a table of the same name starts over.
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) async fn drop_table(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    for statement in [
+        format!("DROP TABLE IF EXISTS {}", queries.table),
+        format!("DROP TABLE IF EXISTS {}", queries.revision_table),
+        format!("DROP TABLE IF EXISTS {}", queries.outbox_table),
+    ] {
+        sqlx::query(&statement).execute(conn).await.adapt()?;
+    }
+
+    create_migrations_table(conn).await?;
+    sqlx::query(&queries.forget_migrations)
+        .bind(&*queries.table_name)
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .adapt()
+}
+
+async fn create_migrations_table(conn: &ConnectionPool) -> Result<()> {
     sqlx::query(&format!(
-        "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    v0 = $2 AND
-                    v1 = $3 AND
-                    v2 = $4 AND
-                    v3 = $5 AND
-                    v4 = $6 AND
-                    v5 = $7",
-        table_name
+        "CREATE TABLE IF NOT EXISTS {} (
+                    table_name VARCHAR(255) NOT NULL,
+                    version BIGINT NOT NULL,
+                    description VARCHAR(255) NOT NULL,
+                    PRIMARY KEY (table_name, version)
+                    )",
+        MIGRATIONS_TABLE
     ))
-    .bind(pt)
-    .bind(&rule[0])
-    .bind(&rule[1])
-    .bind(&rule[2])
-    .bind(&rule[3])
-    .bind(&rule[4])
-    .bind(&rule[5])
     .execute(conn)
     .await
-    .map(|n| PgQueryResult::rows_affected(&n) == 1)
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .map(|_| ())
+    .adapt()
 }
 
-#[cfg(feature = "sqlite")]
-pub async fn remove_policy(
-    conn: &ConnectionPool,
-    table_name: &str,
-    pt: &str,
-    rule: Vec<String>,
-) -> Result<bool> {
-    let rule = normalize_casbin_rule(rule);
+pub async fn run_migrations(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    create_migrations_table(conn).await?;
+
+    let applied: Vec<i64> = sqlx::query_scalar(&queries.applied_migrations)
+        .bind(&*queries.table_name)
+        .fetch_all(conn)
+        .await
+        .adapt()?;
+
+    for (version, description) in MIGRATIONS.iter() {
+        if applied.contains(version) {
+            continue;
+        }
+
+        apply_migration(conn, queries, *version).await?;
+        sqlx::query(&queries.record_migration)
+            .bind(&*queries.table_name)
+            .bind(version)
+            .bind(description)
+            .execute(conn)
+            .await
+            .adapt()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "mysql")]
+pub async fn widen_ptype_column(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
     sqlx::query(&format!(
-        "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    v0 = $2 AND
-                    v1 = $3 AND
-                    v2 = $4 AND
-                    v3 = $5 AND
-                    v4 = $6 AND
-                    v5 = $7",
-        table_name
+        "ALTER TABLE {} MODIFY ptype VARCHAR({}) NOT NULL",
+        queries.table, PTYPE_LENGTH
     ))
-    .bind(pt)
-    .bind(&rule[0])
-    .bind(&rule[1])
-    .bind(&rule[2])
-    .bind(&rule[3])
-    .bind(&rule[4])
-    .bind(&rule[5])
     .execute(conn)
     .await
-    .map(|n| SqliteQueryResult::rows_affected(&n) == 1)
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .map(|_| ())
+    .adapt()
 }
 
 #[cfg(feature = "mysql")]
-pub async fn remove_policy(
-    conn: &ConnectionPool,
-    table_name: &str,
-    pt: &str,
-    rule: Vec<String>,
-) -> Result<bool> {
-    let rule = normalize_casbin_rule(rule);
+pub async fn migrate_to_bigint_ids(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
     sqlx::query(&format!(
-        "DELETE FROM {} WHERE
-                    ptype = ? AND
-                    v0 = ? AND
-                    v1 = ? AND
-                    v2 = ? AND
-                    v3 = ? AND
-                    v4 = ? AND
-                    v5 = ?",
-        table_name
+        "ALTER TABLE {} MODIFY id BIGINT NOT NULL AUTO_INCREMENT",
+        queries.table
     ))
-    .bind(pt)
-    .bind(&rule[0])
-    .bind(&rule[1])
-    .bind(&rule[2])
-    .bind(&rule[3])
-    .bind(&rule[4])
-    .bind(&rule[5])
     .execute(conn)
     .await
-    .map(|n| MySqlQueryResult::rows_affected(&n) == 1)
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .map(|_| ())
+    .adapt()
 }
 
-#[cfg(feature = "postgres")]
-pub async fn remove_policies(
+// The binary collation of the table's own character set compares values
+// byte-wise, matching how casbin compares rules in memory. Tables keep their
+// character set: moving utf8 ones to utf8mb4 would push the unique key past
+// InnoDB's 3072-byte limit, and utf8mb4 ones would lose characters in utf8.
+#[cfg(feature = "mysql")]
+pub async fn alter_collation(conn: &ConnectionPool, queries: &Queries) -> Result<MySqlQueryResult> {
+    let (charset,): (String,) = sqlx::query_as(
+        "SELECT CAST(SUBSTRING_INDEX(TABLE_COLLATION, '_', 1) AS CHAR)
+            FROM information_schema.TABLES
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?",
+    )
+    .bind(&*queries.table_name)
+    .fetch_one(conn)
+    .await
+    .adapt()?;
+    sqlx::query(&format!(
+        "ALTER TABLE {} CONVERT TO CHARACTER SET {1} COLLATE {1}_bin",
+        queries.table, charset
+    ))
+    .execute(conn)
+    .await
+    .adapt()
+}
+
+pub async fn create_index(
     conn: &ConnectionPool,
-    table_name: &str,
-    pt: &str,
-    rules: Vec<Vec<String>>,
-) -> Result<bool> {
-    let mut transaction = conn
-        .begin()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    for rule in rules {
-        let rule = normalize_casbin_rule(rule);
-        sqlx::query(&format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    v0 = $2 AND
-                    v1 = $3 AND
-                    v2 = $4 AND
-                    v3 = $5 AND
-                    v4 = $6 AND
-                    v5 = $7",
-            table_name
-        ))
-        .bind(pt)
-        .bind(&rule[0])
-        .bind(&rule[1])
-        .bind(&rule[2])
-        .bind(&rule[3])
-        .bind(&rule[4])
-        .bind(&rule[5])
-        .execute(&mut *transaction)
-        .await
-        .and_then(|n| {
-            if PgQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
-            } else {
-                Err(SqlxError::RowNotFound)
-            }
-        })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+    queries: &Queries,
+    spec: &IndexSpec,
+) -> Result<()> {
+    create_named_index(
+        conn,
+        queries,
+        &spec.name(&queries.table_name),
+        &spec.column_list(),
+        false,
+    )
+    .await
+}
+
+pub async fn drop_index(conn: &ConnectionPool, queries: &Queries, spec: &IndexSpec) -> Result<()> {
+    drop_named_index(conn, queries, &spec.name(&queries.table_name)).await
+}
+
+fn create_index_sql(unique: bool) -> &'static str {
+    if unique {
+        "CREATE UNIQUE INDEX"
+    } else {
+        "CREATE INDEX"
     }
-    transaction
-        .commit()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(true)
 }
 
-#[cfg(feature = "sqlite")]
-pub async fn remove_policies(
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub(crate) async fn create_named_index(
     conn: &ConnectionPool,
-    table_name: &str,
-    pt: &str,
-    rules: Vec<Vec<String>>,
-) -> Result<bool> {
-    let mut transaction = conn
-        .begin()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    for rule in rules {
-        let rule = normalize_casbin_rule(rule);
-        sqlx::query(&format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    v0 = $2 AND
-                    v1 = $3 AND
-                    v2 = $4 AND
-                    v3 = $5 AND
-                    v4 = $6 AND
-                    v5 = $7",
-            table_name
-        ))
-        .bind(pt)
-        .bind(&rule[0])
-        .bind(&rule[1])
-        .bind(&rule[2])
-        .bind(&rule[3])
-        .bind(&rule[4])
-        .bind(&rule[5])
-        .execute(&mut *transaction)
+    queries: &Queries,
+    name: &str,
+    columns: &str,
+    unique: bool,
+) -> Result<()> {
+    sqlx::query(&format!(
+        "{} IF NOT EXISTS {} ON {} ({})",
+        create_index_sql(unique),
+        quote(name),
+        queries.table,
+        columns
+    ))
+    .execute(conn)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+// Index names are per schema here, not per table.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub(crate) async fn drop_named_index(
+    conn: &ConnectionPool,
+    _queries: &Queries,
+    name: &str,
+) -> Result<()> {
+    sqlx::query(&format!("DROP INDEX IF EXISTS {}", quote(name)))
+        .execute(conn)
         .await
-        .and_then(|n| {
-            if SqliteQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
-            } else {
-                Err(SqlxError::RowNotFound)
-            }
-        })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+        .map(|_| ())
+        .adapt()
+}
+
+// MySQL has no IF [NOT] EXISTS for indexes, so the errors for an existing
+// (1061) or missing (1091) index are treated as success instead.
+#[cfg(feature = "mysql")]
+fn is_mysql_error(err: &SqlxError, number: u16) -> bool {
+    err.as_database_error()
+        .and_then(|err| err.try_downcast_ref::<MySqlDatabaseError>())
+        .is_some_and(|err| err.number() == number)
+}
+
+#[cfg(feature = "mysql")]
+pub(crate) async fn create_named_index(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    name: &str,
+    columns: &str,
+    unique: bool,
+) -> Result<()> {
+    match sqlx::query(&format!(
+        "{} {} ON {} ({})",
+        create_index_sql(unique),
+        quote(name),
+        queries.table,
+        columns
+    ))
+    .execute(conn)
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(err) if is_mysql_error(&err, 1061) => Ok(()),
+        Err(err) => Err(err).adapt(),
     }
-    transaction
-        .commit()
+}
+
+#[cfg(feature = "mysql")]
+pub(crate) async fn drop_named_index(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    name: &str,
+) -> Result<()> {
+    match sqlx::query(&format!("DROP INDEX {} ON {}", quote(name), queries.table))
+        .execute(conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(true)
+    {
+        Ok(_) => Ok(()),
+        Err(err) if is_mysql_error(&err, 1091) => Ok(()),
+        Err(err) => Err(err).adapt(),
+    }
+}
+
+// One row per indexed column in index order, the column is None for
+// expressions.
+type IndexColumn = (String, bool, Option<String>);
+
+#[cfg(feature = "postgres")]
+async fn index_columns(conn: &ConnectionPool, queries: &Queries) -> Result<Vec<IndexColumn>> {
+    sqlx::query_as(
+        "SELECT CAST(c.relname AS TEXT), ix.indisunique, CAST(a.attname AS TEXT)
+            FROM pg_index ix
+            JOIN pg_class c ON c.oid = ix.indexrelid
+            CROSS JOIN LATERAL unnest(CAST(ix.indkey AS INT2[])) WITH ORDINALITY AS k(attnum, n)
+            LEFT JOIN pg_attribute a ON a.attrelid = ix.indrelid AND a.attnum = k.attnum
+            WHERE ix.indrelid = to_regclass($1)
+            ORDER BY c.relname, k.n",
+    )
+    .bind(&*queries.table)
+    .fetch_all(conn)
+    .await
+    .adapt()
 }
 
 #[cfg(feature = "mysql")]
-pub async fn remove_policies(
+async fn index_columns(conn: &ConnectionPool, queries: &Queries) -> Result<Vec<IndexColumn>> {
+    let rows: Vec<(String, i64, Option<String>)> = sqlx::query_as(
+        "SELECT CAST(index_name AS CHAR), CAST(non_unique AS SIGNED), CAST(column_name AS CHAR)
+            FROM information_schema.statistics
+            WHERE table_schema = DATABASE() AND table_name = ?
+            ORDER BY index_name, seq_in_index",
+    )
+    .bind(&*queries.table_name)
+    .fetch_all(conn)
+    .await
+    .adapt()?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(name, non_unique, column)| (name, non_unique == 0, column))
+        .collect())
+}
+
+#[cfg(feature = "sqlite")]
+async fn index_columns(conn: &ConnectionPool, queries: &Queries) -> Result<Vec<IndexColumn>> {
+    let rows: Vec<(String, i64, Option<String>)> = sqlx::query_as(
+        "SELECT il.name, il.\"unique\", ii.name
+            FROM pragma_index_list(?1) il
+            JOIN pragma_index_info(il.name) ii
+            ORDER BY il.name, ii.seqno",
+    )
+    .bind(&*queries.table_name)
+    .fetch_all(conn)
+    .await
+    .adapt()?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(name, unique, column)| (name, unique != 0, column))
+        .collect())
+}
+
+pub(crate) async fn list_indexes(
     conn: &ConnectionPool,
-    table_name: &str,
-    pt: &str,
-    rules: Vec<Vec<String>>,
-) -> Result<bool> {
-    let mut transaction = conn
-        .begin()
+    queries: &Queries,
+) -> Result<Vec<TableIndex>> {
+    let mut indexes: Vec<TableIndex> = Vec::new();
+    for (name, unique, column) in index_columns(conn, queries).await? {
+        let column = column.unwrap_or_default();
+        match indexes.last_mut() {
+            Some(index) if index.name == name => index.columns.push(column),
+            _ => indexes.push(TableIndex {
+                name,
+                columns: vec![column],
+                unique,
+            }),
+        }
+    }
+    Ok(indexes)
+}
+
+#[cfg(feature = "postgres")]
+pub async fn add_timestamp_columns(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    for statement in timestamp_columns(Backend::CURRENT, &queries.table, &queries.table_name) {
+        sqlx::query(&statement).execute(conn).await.adapt()?;
+    }
+    Ok(())
+}
+
+// A duplicate column (1060) means the table has been migrated before.
+#[cfg(feature = "mysql")]
+pub async fn add_timestamp_columns(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    for statement in timestamp_columns(Backend::CURRENT, &queries.table, &queries.table_name) {
+        match sqlx::query(&statement).execute(conn).await {
+            Ok(_) => {}
+            Err(err) if is_mysql_error(&err, 1060) => {}
+            Err(err) => return Err(err).adapt(),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+pub async fn add_timestamp_columns(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut transaction = conn.begin().await.adapt()?;
+
+    if has_column(&mut transaction, queries, "created_at").await? {
+        return Ok(());
+    }
+
+    let statements = timestamp_columns(Backend::CURRENT, &queries.table, &queries.table_name);
+    execute_all(&mut transaction, &statements).await?;
+
+    transaction.commit().await.adapt()
+}
+
+// Columns the adapter's optional modes need are added by migrations, which
+// check for them first.
+#[cfg(feature = "postgres")]
+async fn has_column(conn: &mut DbConnection, queries: &Queries, column: &str) -> Result<bool> {
+    sqlx::query(
+        "SELECT 1 FROM pg_attribute
+            WHERE attrelid = $1::regclass AND attname = $2 AND NOT attisdropped",
+    )
+    .bind(&*queries.table)
+    .bind(column)
+    .fetch_optional(conn)
+    .await
+    .map(|row| row.is_some())
+    .adapt()
+}
+
+#[cfg(feature = "mysql")]
+async fn has_column(conn: &mut DbConnection, queries: &Queries, column: &str) -> Result<bool> {
+    sqlx::query(
+        "SELECT 1 FROM information_schema.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_NAME = ?",
+    )
+    .bind(&*queries.table_name)
+    .bind(column)
+    .fetch_optional(conn)
+    .await
+    .map(|row| row.is_some())
+    .adapt()
+}
+
+#[cfg(feature = "sqlite")]
+async fn has_column(conn: &mut DbConnection, queries: &Queries, column: &str) -> Result<bool> {
+    sqlx::query("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")
+        .bind(&*queries.table_name)
+        .bind(column)
+        .fetch_optional(conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    for rule in rules {
-        let rule = normalize_casbin_rule(rule);
+        .map(|row| row.is_some())
+        .adapt()
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+async fn execute_all(conn: &mut DbConnection, statements: &[String]) -> Result<()> {
+    for statement in statements {
+        sqlx::query(statement).execute(&mut *conn).await.adapt()?;
+    }
+    Ok(())
+}
+
+// The unique key over a rule, led by its tenant once rules are kept per
+// tenant. With soft deletes it only covers the rules not deleted, so that a
+// removed rule can be added again.
+fn unique_key_columns(tenant: bool) -> &'static str {
+    if tenant {
+        "tenant_id, ptype, v0, v1, v2, v3, v4, v5"
+    } else {
+        "ptype, v0, v1, v2, v3, v4, v5"
+    }
+}
+
+// A partial unique index under the constraint's name stands in for the
+// constraint with soft deletes.
+#[cfg(feature = "postgres")]
+fn replace_unique_key(queries: &Queries, tenant: bool, soft_delete: bool) -> Vec<String> {
+    let name = quote(&queries.unique_constraint);
+    vec![
+        format!(
+            "ALTER TABLE {} DROP CONSTRAINT IF EXISTS {}",
+            queries.table, name
+        ),
+        format!("DROP INDEX IF EXISTS {}", name),
+        if soft_delete {
+            format!(
+                "CREATE UNIQUE INDEX {} ON {} ({}) WHERE deleted_at IS NULL",
+                name,
+                queries.table,
+                unique_key_columns(tenant)
+            )
+        } else {
+            format!(
+                "ALTER TABLE {} ADD CONSTRAINT {} UNIQUE({})",
+                queries.table,
+                name,
+                unique_key_columns(tenant)
+            )
+        },
+    ]
+}
+
+#[cfg(feature = "postgres")]
+pub async fn migrate_to_soft_delete(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut transaction = conn.begin().await.adapt()?;
+
+    if has_column(&mut transaction, queries, "deleted_at").await? {
+        return Ok(());
+    }
+    let tenant = has_column(&mut transaction, queries, "tenant_id").await?;
+
+    let mut statements = vec![format!(
+        "ALTER TABLE {} ADD COLUMN deleted_at TIMESTAMPTZ",
+        queries.table
+    )];
+    statements.extend(replace_unique_key(queries, tenant, true));
+    execute_all(&mut transaction, &statements).await?;
+
+    transaction.commit().await.adapt()
+}
+
+#[cfg(feature = "postgres")]
+pub async fn migrate_to_tenants(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut transaction = conn.begin().await.adapt()?;
+
+    if has_column(&mut transaction, queries, "tenant_id").await? {
+        return Ok(());
+    }
+    let soft_delete = has_column(&mut transaction, queries, "deleted_at").await?;
+
+    let mut statements = vec![format!(
+        "ALTER TABLE {} ADD COLUMN tenant_id VARCHAR NOT NULL DEFAULT ''",
+        queries.table
+    )];
+    statements.extend(replace_unique_key(queries, true, soft_delete));
+    execute_all(&mut transaction, &statements).await?;
+
+    transaction.commit().await.adapt()
+}
+
+// Room for tenant ids, within InnoDB's limit on the unique key.
+#[cfg(feature = "mysql")]
+const TENANT_LENGTH: usize = 64;
+
+// MySQL has no partial indexes. With soft deletes the unique key ends in a
+// generated column that is 1 for rules not deleted and NULL otherwise, and
+// NULLs never collide.
+#[cfg(feature = "mysql")]
+fn replace_unique_key(queries: &Queries, tenant: bool, soft_delete: bool) -> String {
+    format!(
+        "DROP INDEX {0}, ADD CONSTRAINT {0} UNIQUE({1}{2})",
+        quote(&queries.unique_constraint),
+        unique_key_columns(tenant),
+        if soft_delete { ", not_deleted" } else { "" }
+    )
+}
+
+#[cfg(feature = "mysql")]
+pub async fn migrate_to_soft_delete(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    if has_column(&mut connection, queries, "deleted_at").await? {
+        return Ok(());
+    }
+    let tenant = has_column(&mut connection, queries, "tenant_id").await?;
+
+    sqlx::query(&format!(
+        "ALTER TABLE {}
+            ADD COLUMN deleted_at DATETIME(6) NULL,
+            ADD COLUMN not_deleted TINYINT AS (IF(deleted_at IS NULL, 1, NULL)) VIRTUAL,
+            {}",
+        queries.table,
+        replace_unique_key(queries, tenant, true)
+    ))
+    .execute(&mut *connection)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+#[cfg(feature = "mysql")]
+pub async fn migrate_to_tenants(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    if has_column(&mut connection, queries, "tenant_id").await? {
+        return Ok(());
+    }
+    let soft_delete = has_column(&mut connection, queries, "deleted_at").await?;
+
+    sqlx::query(&format!(
+        "ALTER TABLE {}
+            ADD COLUMN tenant_id VARCHAR({}) NOT NULL DEFAULT '',
+            {}",
+        queries.table,
+        TENANT_LENGTH,
+        replace_unique_key(queries, true, soft_delete)
+    ))
+    .execute(&mut *connection)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+// SQLite cannot drop a table's constraints, so the table is rebuilt with the
+// new column, column by column as it stands but without the unique
+// constraint. Its indexes and triggers are recreated on the new table, and
+// the unique key returns as an index under the constraint's name.
+#[cfg(feature = "sqlite")]
+async fn rebuild_with_column(
+    conn: &mut DbConnection,
+    queries: &Queries,
+    column: &str,
+    tenant: bool,
+    soft_delete: bool,
+) -> Result<()> {
+    let columns: Vec<(String, String, bool, Option<String>, bool)> = sqlx::query_as(
+        "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1) ORDER BY cid",
+    )
+    .bind(&*queries.table_name)
+    .fetch_all(&mut *conn)
+    .await
+    .adapt()?;
+
+    let schema: Vec<String> = sqlx::query_scalar(
+        "SELECT sql FROM sqlite_master
+            WHERE tbl_name = ?1 AND name != ?2 AND type IN ('index', 'trigger')
+                AND sql IS NOT NULL",
+    )
+    .bind(&*queries.table_name)
+    .bind(&*queries.unique_constraint)
+    .fetch_all(&mut *conn)
+    .await
+    .adapt()?;
+
+    let rebuilt = quote(&identifier(format!(
+        "sqlx_adapter_rebuild_{}",
+        queries.table_name
+    )));
+    let definitions = columns
+        .iter()
+        .map(|(name, data_type, not_null, default, pk)| {
+            let mut definition = format!("{} {}", quote(name), data_type);
+            if *pk {
+                definition.push_str(" PRIMARY KEY");
+            }
+            if *not_null {
+                definition.push_str(" NOT NULL");
+            }
+            // The pragma drops the parentheses around expression defaults.
+            if let Some(default) = default {
+                definition.push_str(&format!(" DEFAULT ({})", default));
+            }
+            definition
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+    let names = columns
+        .iter()
+        .map(|(name, ..)| quote(name))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let mut statements = vec![
+        format!("CREATE TABLE {} ({}, {})", rebuilt, definitions, column),
+        format!(
+            "INSERT INTO {} ({2}) SELECT {2} FROM {1}",
+            rebuilt, queries.table, names
+        ),
+        format!("DROP TABLE {}", queries.table),
+        format!("ALTER TABLE {} RENAME TO {}", rebuilt, queries.table),
+    ];
+    statements.extend(schema);
+    statements.push(format!(
+        "CREATE UNIQUE INDEX {} ON {} ({}){}",
+        quote(&queries.unique_constraint),
+        queries.table,
+        unique_key_columns(tenant),
+        if soft_delete {
+            " WHERE deleted_at IS NULL"
+        } else {
+            ""
+        }
+    ));
+
+    execute_all(conn, &statements).await
+}
+
+#[cfg(feature = "sqlite")]
+pub async fn migrate_to_soft_delete(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut transaction = conn.begin().await.adapt()?;
+
+    if has_column(&mut transaction, queries, "deleted_at").await? {
+        return Ok(());
+    }
+    let tenant = has_column(&mut transaction, queries, "tenant_id").await?;
+    rebuild_with_column(&mut transaction, queries, "deleted_at TEXT", tenant, true).await?;
+
+    transaction.commit().await.adapt()
+}
+
+#[cfg(feature = "sqlite")]
+pub async fn migrate_to_tenants(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut transaction = conn.begin().await.adapt()?;
+
+    if has_column(&mut transaction, queries, "tenant_id").await? {
+        return Ok(());
+    }
+    let soft_delete = has_column(&mut transaction, queries, "deleted_at").await?;
+    rebuild_with_column(
+        &mut transaction,
+        queries,
+        "tenant_id VARCHAR NOT NULL DEFAULT ''",
+        true,
+        soft_delete,
+    )
+    .await?;
+
+    transaction.commit().await.adapt()
+}
+
+#[cfg(feature = "postgres")]
+const METADATA_TYPE: &str = "JSONB";
+
+#[cfg(feature = "mysql")]
+const METADATA_TYPE: &str = "JSON";
+
+#[cfg(feature = "sqlite")]
+const METADATA_TYPE: &str = "TEXT";
+
+pub async fn add_metadata_column(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    if has_column(&mut connection, queries, "metadata").await? {
+        return Ok(());
+    }
+
+    sqlx::query(&format!(
+        "ALTER TABLE {} ADD COLUMN metadata {}",
+        queries.table, METADATA_TYPE
+    ))
+    .execute(&mut *connection)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+// BOOLEAN is a synonym for TINYINT(1) on MySQL and takes the integer
+// affinity on SQLite, TRUE and FALSE work on all three.
+pub async fn add_enabled_column(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    if has_column(&mut connection, queries, "enabled").await? {
+        return Ok(());
+    }
+
+    sqlx::query(&format!(
+        "ALTER TABLE {} ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT TRUE",
+        queries.table
+    ))
+    .execute(&mut *connection)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+#[cfg(feature = "postgres")]
+const EXPIRES_AT_TYPE: &str = "TIMESTAMPTZ";
+
+#[cfg(feature = "mysql")]
+const EXPIRES_AT_TYPE: &str = "DATETIME(6) NULL";
+
+// Stored as text in the form of NOW, which compares in time order.
+#[cfg(feature = "sqlite")]
+const EXPIRES_AT_TYPE: &str = "TEXT";
+
+pub async fn add_expiry_column(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    if has_column(&mut connection, queries, "expires_at").await? {
+        return Ok(());
+    }
+
+    sqlx::query(&format!(
+        "ALTER TABLE {} ADD COLUMN expires_at {}",
+        queries.table, EXPIRES_AT_TYPE
+    ))
+    .execute(&mut *connection)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+pub async fn add_modified_by_column(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    if has_column(&mut connection, queries, "modified_by").await? {
+        return Ok(());
+    }
+
+    sqlx::query(&format!(
+        "ALTER TABLE {} ADD COLUMN modified_by VARCHAR(255)",
+        queries.table
+    ))
+    .execute(&mut *connection)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+pub async fn add_description_column(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    if has_column(&mut connection, queries, "description").await? {
+        return Ok(());
+    }
+
+    sqlx::query(&format!(
+        "ALTER TABLE {} ADD COLUMN description TEXT",
+        queries.table
+    ))
+    .execute(&mut *connection)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+// Rows stored before the column, or by adapters without `with_rule_hash`,
+// are hashed a batch per transaction until none is left.
+pub async fn add_rule_hash_column(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    if !has_column(&mut connection, queries, "rule_hash").await? {
         sqlx::query(&format!(
-            "DELETE FROM {} WHERE
-                    ptype = ? AND
-                    v0 = ? AND
-                    v1 = ? AND
-                    v2 = ? AND
-                    v3 = ? AND
-                    v4 = ? AND
-                    v5 = ?",
-            table_name
+            "ALTER TABLE {} ADD COLUMN rule_hash BIGINT",
+            queries.table
         ))
-        .bind(pt)
-        .bind(&rule[0])
-        .bind(&rule[1])
-        .bind(&rule[2])
-        .bind(&rule[3])
-        .bind(&rule[4])
-        .bind(&rule[5])
-        .execute(&mut *transaction)
+        .execute(&mut *connection)
         .await
-        .and_then(|n| {
-            if MySqlQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
-            } else {
-                Err(SqlxError::RowNotFound)
-            }
-        })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+        .adapt()?;
+    }
+
+    loop {
+        let mut transaction = begin(&mut connection, None).await?;
+        let rows: Vec<CasbinRule> = sqlx::query_as(&queries.unhashed)
+            .fetch_all(&mut *transaction)
+            .await
+            .map_err(map_transaction_error)?;
+        if rows.is_empty() {
+            break;
+        }
+
+        for row in &rows {
+            let values = [row.v0.as_str(), &row.v1, &row.v2, &row.v3, &row.v4, &row.v5];
+            sqlx::query(&queries.backfill_hash)
+                .bind(rule_hash(&row.ptype, values))
+                .bind(&row.ptype)
+                .bind(values[0])
+                .bind(values[1])
+                .bind(values[2])
+                .bind(values[3])
+                .bind(values[4])
+                .bind(values[5])
+                .execute(&mut *transaction)
+                .await
+                .map_err(map_transaction_error)?;
+        }
+        transaction.commit().await.map_err(map_commit_error)?;
+    }
+
+    let index = crate::queries::identifier(format!("idx_{}_rule_hash", queries.table_name));
+    create_named_index(conn, queries, &index, "rule_hash", false).await
+}
+
+// The revision table holds a single row, created along with the table.
+pub async fn create_revision_table(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let statements = [
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                    id INTEGER NOT NULL PRIMARY KEY,
+                    revision BIGINT NOT NULL
+                    )",
+            queries.revision_table
+        ),
+        queries.seed_revision.to_string(),
+    ];
+
+    for statement in statements.iter() {
+        sqlx::query(statement).execute(conn).await.adapt()?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "postgres")]
+const OUTBOX_COLUMNS: &str = "id BIGSERIAL PRIMARY KEY,
+                    op VARCHAR(32) NOT NULL,
+                    payload JSONB NOT NULL,
+                    created_at TIMESTAMPTZ NOT NULL DEFAULT now()";
+
+#[cfg(feature = "mysql")]
+const OUTBOX_COLUMNS: &str = "id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+                    op VARCHAR(32) NOT NULL,
+                    payload JSON NOT NULL,
+                    created_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6)";
+
+// AUTOINCREMENT keeps the ids of acknowledged events from being handed out
+// again, which a relay remembering the last id it saw would skip.
+#[cfg(feature = "sqlite")]
+const OUTBOX_COLUMNS: &str = "id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    op TEXT NOT NULL,
+                    payload TEXT NOT NULL,
+                    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now'))";
+
+pub async fn create_outbox_table(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} ({})",
+        queries.outbox_table, OUTBOX_COLUMNS
+    ))
+    .execute(conn)
+    .await
+    .map(|_| ())
+    .adapt()
+}
+
+// The id, operation, payload and creation time in epoch microseconds of the
+// oldest events.
+pub(crate) async fn poll_outbox(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    limit: u64,
+) -> Result<Vec<(i64, String, Json<Value>, i64)>> {
+    sqlx::query_as(&queries.poll_outbox)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(conn)
+        .await
+        .adapt()
+}
+
+pub(crate) async fn ack_outbox(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    ids: &[i64],
+) -> Result<u64> {
+    let mut acked = 0;
+    for chunk in ids.chunks(MAX_RULES_PER_STATEMENT) {
+        let mut query = QueryBuilder::<Db>::new(format!(
+            "DELETE FROM {} WHERE id IN (",
+            queries.outbox_table
+        ));
+        let mut separated = query.separated(", ");
+        for id in chunk {
+            separated.push_bind(id);
+        }
+        query.push(")");
+        acked += query
+            .build()
+            .persistent(false)
+            .execute(conn)
+            .await
+            .adapt()?
+            .rows_affected();
     }
-    transaction
-        .commit()
+    Ok(acked)
+}
+
+pub async fn current_revision(conn: &ConnectionPool, queries: &Queries) -> Result<i64> {
+    sqlx::query_scalar(&queries.current_revision)
+        .fetch_one(conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(true)
+        .adapt()
 }
 
+// Purging removes rules that were already gone from the policy, so it leaves
+// the revision alone.
+pub async fn purge_deleted(conn: &ConnectionPool, queries: &Queries, before: i64) -> Result<u64> {
+    sqlx::query(&queries.purge_deleted)
+        .bind_tenant(queries)
+        .bind(before)
+        .execute(conn)
+        .await
+        .map(|n| n.rows_affected())
+        .adapt()
+}
+
+pub async fn purge_expired(conn: &ConnectionPool, queries: &Queries) -> Result<u64> {
+    sqlx::query(&queries.purge_expired)
+        .bind_tenant(queries)
+        .execute(conn)
+        .await
+        .map(|n| n.rows_affected())
+        .adapt()
+}
+
+#[allow(dead_code)]
 #[cfg(feature = "postgres")]
-pub async fn remove_filtered_policy(
+pub async fn new(conn: &ConnectionPool) -> Result<PgQueryResult> {
+    new_with_table_name(conn, &Queries::new("casbin_rule")).await
+}
+
+#[allow(dead_code)]
+#[cfg(feature = "sqlite")]
+pub async fn new(conn: &ConnectionPool) -> Result<SqliteQueryResult> {
+    new_with_table_name(conn, &Queries::new("casbin_rule")).await
+}
+
+#[allow(dead_code)]
+#[cfg(feature = "mysql")]
+pub async fn new(conn: &ConnectionPool) -> Result<MySqlQueryResult> {
+    new_with_table_name(conn, &Queries::new("casbin_rule")).await
+}
+
+// Checked at build time for the default table with `compile-checked`.
+fn remove_query<'q>(
+    queries: &'q Queries,
+    pt: &'q str,
+    rule: [&'q str; 6],
+) -> Query<'q, Db, <Db as Database>::Arguments<'q>> {
+    #[cfg(all(feature = "compile-checked", feature = "postgres"))]
+    if queries.is_static() {
+        return checked::remove(pt, rule);
+    }
+
+    sqlx::query(&queries.remove)
+        .bind_tenant(queries)
+        .bind(pt)
+        .bind(rule[0])
+        .bind(rule[1])
+        .bind(rule[2])
+        .bind(rule[3])
+        .bind(rule[4])
+        .bind(rule[5])
+        .bind_lookup_hash(queries, pt, rule)
+}
+
+pub async fn remove_policy(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
     pt: &str,
-    field_index: usize,
-    field_values: Vec<String>,
+    rule: &[String],
 ) -> Result<bool> {
-    let field_values = normalize_casbin_rule_option(field_values);
-    let query = if field_index == 5 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    (v5 is NULL OR v5 = COALESCE($2,v5))",
-            table_name
-        )
-    } else if field_index == 4 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    (v4 is NULL OR v4 = COALESCE($2,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE($3,v5))",
-            table_name
-        )
-    } else if field_index == 3 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    (v3 is NULL OR v3 = COALESCE($2,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE($3,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE($4,v5))",
-            table_name
-        )
-    } else if field_index == 2 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    (v2 is NULL OR v2 = COALESCE($2,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE($3,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE($4,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE($5,v5))",
-            table_name
-        )
-    } else if field_index == 1 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    (v1 is NULL OR v1 = COALESCE($2,v1)) AND
-                    (v2 is NULL OR v2 = COALESCE($3,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE($4,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE($5,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE($6,v5))",
-            table_name
-        )
-    } else {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    (v0 is NULL OR v0 = COALESCE($2,v0)) AND
-                    (v1 is NULL OR v1 = COALESCE($3,v1)) AND
-                    (v2 is NULL OR v2 = COALESCE($4,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE($5,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE($6,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE($7,v5))",
-            table_name
-        )
-    };
+    let rule = normalize_casbin_rule(rule);
+    let query = remove_query(queries, pt, rule);
+
+    execute_mutation(
+        conn,
+        queries,
+        query,
+        |n| n.rows_affected() >= 1,
+        |err| CasbinError::from(Error::from(err)),
+        Change::rule(Operation::Remove, pt, rule),
+    )
+    .await
+    .map(|n| n.rows_affected() >= 1)
+}
 
-    let mut q = sqlx::query(&query).bind(pt);
-    for value in field_values.iter().take(6 - field_index) {
-        q = q.bind(value);
-    }
+#[cfg(feature = "postgres")]
+pub async fn policy_exists(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    pt: &str,
+    rule: &[String],
+) -> Result<bool> {
+    let rule = normalize_casbin_rule(rule);
+    sqlx::query_scalar(&queries.exists)
+        .bind_tenant(queries)
+        .bind(pt)
+        .bind(rule[0])
+        .bind(rule[1])
+        .bind(rule[2])
+        .bind(rule[3])
+        .bind(rule[4])
+        .bind(rule[5])
+        .bind_lookup_hash(queries, pt, rule)
+        .fetch_one(conn)
+        .await
+        .adapt()
+}
 
-    q.execute(conn)
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub async fn policy_exists(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    pt: &str,
+    rule: &[String],
+) -> Result<bool> {
+    let rule = normalize_casbin_rule(rule);
+    sqlx::query(&queries.exists)
+        .bind_tenant(queries)
+        .bind(pt)
+        .bind(rule[0])
+        .bind(rule[1])
+        .bind(rule[2])
+        .bind(rule[3])
+        .bind(rule[4])
+        .bind(rule[5])
+        .bind_lookup_hash(queries, pt, rule)
+        .fetch_optional(conn)
         .await
-        .map(|n| PgQueryResult::rows_affected(&n) >= 1)
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+        .map(|row| row.is_some())
+        .adapt()
 }
 
-#[cfg(feature = "sqlite")]
-pub async fn remove_filtered_policy(
+#[cfg(feature = "postgres")]
+pub async fn remove_policies(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
     pt: &str,
-    field_index: usize,
-    field_values: Vec<String>,
+    rules: Vec<Vec<String>>,
+    isolation_level: Option<IsolationLevel>,
 ) -> Result<bool> {
-    let field_values = normalize_casbin_rule_option(field_values);
-    let query = if field_index == 5 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = $1 AND
-                    (v5 is NULL OR v5 = COALESCE(?2,v5))",
-            table_name
-        )
-    } else if field_index == 4 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ?1 AND
-                    (v4 is NULL OR v4 = COALESCE(?2,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?3,v5))",
-            table_name
-        )
-    } else if field_index == 3 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ?1 AND
-                    (v3 is NULL OR v3 = COALESCE(?2,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE(?3,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?4,v5))",
-            table_name
-        )
-    } else if field_index == 2 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ?1 AND
-                    (v2 is NULL OR v2 = COALESCE(?2,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE(?3,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE(?4,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?5,v5))",
-            table_name
-        )
-    } else if field_index == 1 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ?1 AND
-                    (v1 is NULL OR v1 = COALESCE(?2,v1)) AND
-                    (v2 is NULL OR v2 = COALESCE(?3,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE(?4,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE(?5,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?6,v5))",
-            table_name
-        )
-    } else {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ?1 AND
-                    (v0 is NULL OR v0 = COALESCE(?2,v0)) AND
-                    (v1 is NULL OR v1 = COALESCE(?3,v1)) AND
-                    (v2 is NULL OR v2 = COALESCE(?4,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE(?5,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE(?6,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?7,v5))",
-            table_name
-        )
-    };
+    match rules.as_slice() {
+        [] => return Ok(true),
+        [rule] => return remove_policy(conn, queries, pt, rule).await,
+        _ => {}
+    }
 
-    let mut q = sqlx::query(&query).bind(pt);
-    for value in field_values.iter().take(6 - field_index) {
-        q = q.bind(value);
+    let rules = rules
+        .iter()
+        .map(|rule| normalize_casbin_rule(rule))
+        .collect::<Vec<[&str; 6]>>();
+
+    let mut connection = acquire(conn).await?;
+    let mut transaction = begin(&mut connection, isolation_level).await?;
+    for chunk in rules.chunks(MAX_RULES_PER_STATEMENT) {
+        let mut query = QueryBuilder::<Db>::new(&*queries.removal);
+        queries.push_scope(&mut query);
+        query.push(format!(
+            "({}{}) IN (",
+            queries.rule_columns,
+            if queries.options.hash_lookups() {
+                ", rule_hash"
+            } else {
+                ""
+            }
+        ));
+        query.push_values(chunk, |mut row, rule| {
+            for (&column, &value) in Column::ALL.iter().zip(std::iter::once(&pt).chain(rule)) {
+                if queries.options.is_case_insensitive(column) {
+                    row.push("CAST(")
+                        .push_bind_unseparated(value)
+                        .push_unseparated(" AS CITEXT)");
+                } else {
+                    row.push_bind(value);
+                }
+            }
+            if queries.options.hash_lookups() {
+                row.push_bind(rule_hash(pt, *rule));
+            }
+        });
+        query.push(")");
+
+        let removed = query
+            .build()
+            .persistent(false)
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_transaction_error)?
+            .rows_affected();
+
+        // A missing rule leaves the whole batch in place. Duplicates from
+        // tables older than the unique constraint may add to the count.
+        if removed < chunk.len() as u64 {
+            transaction.rollback().await.adapt()?;
+            return Ok(false);
+        }
+    }
+    let rules = rules.into_iter().map(|rule| (pt, rule)).collect();
+    let change = Change::rules(Operation::RemoveMany, Some(pt), rules);
+    record_change(&mut transaction, queries, change).await?;
+    transaction.commit().await.map_err(map_commit_error)?;
+    Ok(true)
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub async fn remove_policies(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    pt: &str,
+    rules: Vec<Vec<String>>,
+    isolation_level: Option<IsolationLevel>,
+) -> Result<bool> {
+    match rules.as_slice() {
+        [] => return Ok(true),
+        [rule] => return remove_policy(conn, queries, pt, rule).await,
+        _ => {}
     }
 
-    q.execute(conn)
-        .await
-        .map(|n| SqliteQueryResult::rows_affected(&n) >= 1)
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    let rules = rules
+        .iter()
+        .map(|rule| normalize_casbin_rule(rule))
+        .collect::<Vec<[&str; 6]>>();
+
+    let mut connection = acquire(conn).await?;
+    let mut transaction = begin(&mut connection, isolation_level).await?;
+    for chunk in rules.chunks(MAX_RULES_PER_STATEMENT) {
+        let mut query = QueryBuilder::<Db>::new(&*queries.removal);
+        queries.push_scope(&mut query);
+        query.push("(");
+        for (i, rule) in chunk.iter().enumerate() {
+            if i > 0 {
+                query.push(" OR ");
+            }
+            query.push("(ptype = ").push_bind(pt);
+            for (column, &value) in queries.columns.iter().zip(rule) {
+                query.push(format!(" AND {} = ", column)).push_bind(value);
+            }
+            if queries.options.hash_lookups() {
+                query
+                    .push(" AND rule_hash = ")
+                    .push_bind(rule_hash(pt, *rule));
+            }
+            query.push(")");
+        }
+        query.push(")");
+
+        let removed = query
+            .build()
+            .persistent(false)
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_transaction_error)?
+            .rows_affected();
+
+        // A missing rule leaves the whole batch in place. Duplicates from
+        // tables older than the unique constraint may add to the count.
+        if removed < chunk.len() as u64 {
+            transaction.rollback().await.adapt()?;
+            return Ok(false);
+        }
+    }
+    let rules = rules.into_iter().map(|rule| (pt, rule)).collect();
+    let change = Change::rules(Operation::RemoveMany, Some(pt), rules);
+    record_change(&mut transaction, queries, change).await?;
+    transaction.commit().await.map_err(map_commit_error)?;
+    Ok(true)
 }
 
-#[cfg(feature = "mysql")]
 pub async fn remove_filtered_policy(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
     pt: &str,
     field_index: usize,
     field_values: Vec<String>,
 ) -> Result<bool> {
-    let field_values = normalize_casbin_rule_option(field_values);
-    let query = if field_index == 5 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ? AND
-                    (v5 is NULL OR v5 = COALESCE(?,v5))",
-            table_name
-        )
-    } else if field_index == 4 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ? AND
-                    (v4 is NULL OR v4 = COALESCE(?,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?,v5))",
-            table_name
-        )
-    } else if field_index == 3 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ? AND
-                    (v3 is NULL OR v3 = COALESCE(?,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE(?,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?,v5))",
-            table_name
-        )
-    } else if field_index == 2 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ? AND
-                    (v2 is NULL OR v2 = COALESCE(?,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE(?,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE(?,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?,v5))",
-            table_name
-        )
-    } else if field_index == 1 {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ? AND
-                    (v1 is NULL OR v1 = COALESCE(?,v1)) AND
-                    (v2 is NULL OR v2 = COALESCE(?,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE(?,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE(?,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?,v5))",
-            table_name
-        )
-    } else {
-        format!(
-            "DELETE FROM {} WHERE
-                    ptype = ? AND
-                    (v0 is NULL OR v0 = COALESCE(?,v0)) AND
-                    (v1 is NULL OR v1 = COALESCE(?,v1)) AND
-                    (v2 is NULL OR v2 = COALESCE(?,v2)) AND
-                    (v3 is NULL OR v3 = COALESCE(?,v3)) AND
-                    (v4 is NULL OR v4 = COALESCE(?,v4)) AND
-                    (v5 is NULL OR v5 = COALESCE(?,v5))",
-            table_name
-        )
-    };
+    let field_values = normalize_casbin_rule_option(&field_values);
 
-    let mut q = sqlx::query(&query).bind(pt);
-    for value in field_values.iter().take(6 - field_index) {
+    // Rules have six values, none of them past v5.
+    let statement = match queries.remove_filtered.get(field_index) {
+        Some(statement) => statement,
+        None => return Ok(false),
+    };
+    let mut q = sqlx::query(&statement.sql).bind_tenant(queries).bind(pt);
+    for &value in field_values.iter().take(statement.values) {
         q = q.bind(value);
     }
 
-    q.execute(conn)
-        .await
-        .map(|n| MySqlQueryResult::rows_affected(&n) >= 1)
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    let filter = field_values[..statement.values]
+        .iter()
+        .map(|value| value.unwrap_or_default())
+        .collect();
+    execute_mutation(
+        conn,
+        queries,
+        q,
+        |n| n.rows_affected() >= 1,
+        |err| CasbinError::from(Error::from(err)),
+        Change::filtered(Operation::RemoveFiltered, pt, field_index, filter),
+    )
+    .await
+    .map(|n| n.rows_affected() >= 1)
 }
 
-fn filtered_where_values<'a>(filter: &Filter<'a>) -> ([&'a str; 6], [&'a str; 6]) {
-    let mut g_filter: [&'a str; 6] = ["%", "%", "%", "%", "%", "%"];
-    let mut p_filter: [&'a str; 6] = ["%", "%", "%", "%", "%", "%"];
-    for (idx, val) in filter.g.iter().enumerate() {
-        if val != &"" {
-            g_filter[idx] = val;
-        }
-    }
-    for (idx, val) in filter.p.iter().enumerate() {
-        if val != &"" {
-            p_filter[idx] = val;
-        }
-    }
-    (g_filter, p_filter)
+pub async fn remove_ptype(conn: &ConnectionPool, queries: &Queries, pt: &str) -> Result<bool> {
+    let query = sqlx::query(&queries.remove_ptype)
+        .bind_tenant(queries)
+        .bind(pt);
+
+    execute_mutation(
+        conn,
+        queries,
+        query,
+        |n| n.rows_affected() >= 1,
+        |err| CasbinError::from(Error::from(err)),
+        Change::all(Operation::RemoveFiltered, Some(pt)),
+    )
+    .await
+    .map(|n| n.rows_affected() >= 1)
 }
 
-#[cfg(feature = "postgres")]
 pub(crate) async fn load_policy(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
+    order: LoadOrder,
 ) -> Result<Vec<CasbinRule>> {
-    let casbin_rule: Vec<CasbinRule> = sqlx::query_as(&format!(
-        "SELECT id, ptype, v0, v1, v2, v3, v4, v5 FROM {}",
-        table_name
-    ))
-    .fetch_all(conn)
-    .await
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+    #[cfg(all(feature = "compile-checked", feature = "postgres"))]
+    if queries.is_static() && order == LoadOrder::Insertion {
+        return checked::load(conn).await.adapt();
+    }
+
+    let casbin_rule: Vec<CasbinRule> = sqlx::query_as(queries.load(order))
+        .bind_tenant(queries)
+        .fetch_all(conn)
+        .await
+        .adapt()?;
 
     Ok(casbin_rule)
 }
 
-#[cfg(feature = "sqlite")]
-pub(crate) async fn load_policy(
+pub(crate) async fn load_timestamped(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
 ) -> Result<Vec<CasbinRule>> {
-    let query = format!(
-        "SELECT id, ptype, v0, v1, v2, v3, v4, v5 FROM {}",
-        table_name
-    );
-
-    let casbin_rule: Vec<CasbinRule> = sqlx::query_as!(CasbinRule, &query)
+    sqlx::query_as(&queries.load_timestamped)
+        .bind_tenant(queries)
         .fetch_all(conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-
-    Ok(casbin_rule)
+        .adapt()
 }
 
-#[cfg(feature = "mysql")]
-pub(crate) async fn load_policy(
+pub(crate) async fn list_page(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
+    offset: i64,
+    limit: i64,
 ) -> Result<Vec<CasbinRule>> {
-    let query = format!(
-        "SELECT id, ptype, v0, v1, v2, v3, v4, v5 FROM {}",
-        table_name
-    );
+    sqlx::query_as(&queries.list_page)
+        .bind_tenant(queries)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(conn)
+        .await
+        .adapt()
+}
 
-    let casbin_rule: Vec<CasbinRule> = sqlx::query_as!(CasbinRule, &query)
+async fn disabled_rules(conn: &mut DbConnection, queries: &Queries) -> Result<Vec<CasbinRule>> {
+    sqlx::query_as(&queries.list_disabled)
+        .bind_tenant(queries)
         .fetch_all(conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+        .map_err(map_transaction_error)
+}
 
-    Ok(casbin_rule)
+pub(crate) async fn list_disabled(
+    conn: &ConnectionPool,
+    queries: &Queries,
+) -> Result<Vec<CasbinRule>> {
+    let mut connection = acquire(conn).await?;
+    disabled_rules(&mut connection, queries).await
 }
 
-#[cfg(feature = "postgres")]
+// Rows are handed over batch by batch and each batch is dropped before the
+// next is read, so peak memory follows the batch size instead of the table.
+pub(crate) async fn load_policy_batched<F>(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    order: LoadOrder,
+    batch_size: usize,
+    mut on_batch: F,
+) -> Result<()>
+where
+    F: FnMut(std::vec::Drain<'_, CasbinRule>) + Send,
+{
+    let mut rows = sqlx::query_as::<_, CasbinRule>(queries.load(order))
+        .bind_tenant(queries)
+        .fetch(conn);
+    let mut batch = Vec::with_capacity(batch_size);
+
+    while let Some(casbin_rule) = rows.try_next().await.adapt()? {
+        batch.push(casbin_rule);
+        if batch.len() == batch_size {
+            on_batch(batch.drain(..));
+        }
+    }
+
+    if !batch.is_empty() {
+        on_batch(batch.drain(..));
+    }
+
+    Ok(())
+}
+
+// The rules as the table hands them out, for callers that pass each on
+// before fetching the next.
+pub(crate) fn export_rules<'c>(
+    conn: &'c ConnectionPool,
+    queries: &'c Queries,
+) -> impl Stream<Item = Result<CasbinRule>> + Send + 'c {
+    sqlx::query_as::<_, CasbinRule>(&queries.export)
+        .bind_tenant(queries)
+        .fetch(conn)
+        .map_err(|err| CasbinError::from(Error::from(err)))
+}
+
+// The rules of one ptype, or all of them, on a single pooled connection that
+// is held until the stream ends or is dropped.
+pub(crate) fn stream_rules<'c>(
+    conn: &'c ConnectionPool,
+    queries: &'c Queries,
+    ptype: Option<&'c str>,
+) -> impl Stream<Item = Result<CasbinRule>> + Send + 'c {
+    let query = match ptype {
+        Some(ptype) => sqlx::query_as::<_, CasbinRule>(&queries.stream_ptype)
+            .bind_tenant(queries)
+            .bind(ptype),
+        None => sqlx::query_as::<_, CasbinRule>(&queries.stream).bind_tenant(queries),
+    };
+    query
+        .fetch(conn)
+        .map_err(|err| CasbinError::from(Error::from(err)))
+}
+
+// The g and p sections and the remaining ptypes are fetched concurrently on
+// three pooled connections. Rows come back grouped in that order and ordered
+// by id within each group.
+// With split tables the g section comes from its own table, `grouping`.
 pub(crate) async fn load_filtered_policy(
     conn: &ConnectionPool,
-    table_name: &str,
+    grouping: &Queries,
+    queries: &Queries,
     filter: &Filter<'_>,
 ) -> Result<Vec<CasbinRule>> {
-    let (g_filter, p_filter) = filtered_where_values(filter);
-
-    let query_string = format!(
-        "SELECT id, ptype, v0, v1, v2, v3, v4, v5 from {} WHERE (
-            ptype LIKE 'g%' AND v0 LIKE $1 AND v1 LIKE $2 AND v2 LIKE $3 AND v3 LIKE $4 AND v4 LIKE $5 AND v5 LIKE $6 )
-        OR (
-            ptype LIKE 'p%' AND v0 LIKE $7 AND v1 LIKE $8 AND v2 LIKE $9 AND v3 LIKE $10 AND v4 LIKE $11 AND v5 LIKE $12 );
-            ",
-        table_name,
-    );
-
-    let casbin_rule: Vec<CasbinRule> = sqlx::query_as(&query_string)
-        .bind(g_filter[0])
-        .bind(g_filter[1])
-        .bind(g_filter[2])
-        .bind(g_filter[3])
-        .bind(g_filter[4])
-        .bind(g_filter[5])
-        .bind(p_filter[0])
-        .bind(p_filter[1])
-        .bind(p_filter[2])
-        .bind(p_filter[3])
-        .bind(p_filter[4])
-        .bind(p_filter[5])
-        .fetch_all(conn)
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+    let mut g_query = QueryBuilder::new("");
+    grouping.push_load_filtered(&mut g_query, "g", &filter.g);
+    let mut p_query = QueryBuilder::new("");
+    queries.push_load_filtered(&mut p_query, "p", &filter.p);
+
+    let (mut casbin_rule, p_rules, other_rules) = try_join3(
+        g_query.build_query_as::<CasbinRule>().fetch_all(conn),
+        p_query.build_query_as::<CasbinRule>().fetch_all(conn),
+        sqlx::query_as::<_, CasbinRule>(&queries.load_unsectioned)
+            .bind_tenant(queries)
+            .fetch_all(conn),
+    )
+    .await
+    .adapt()?;
 
+    casbin_rule.extend(p_rules);
+    casbin_rule.extend(other_rules);
     Ok(casbin_rule)
 }
 
+// Rules are padded to six values and cut off after six, borrowing from the
+// caller instead of cloning into a resized Vec.
+pub(crate) fn normalize_casbin_rule(rule: &[String]) -> [&str; 6] {
+    let mut normalized = [""; 6];
+    for (slot, value) in normalized.iter_mut().zip(rule) {
+        *slot = value;
+    }
+    normalized
+}
+
+pub(crate) fn normalize_casbin_rule_option(rule: &[String]) -> [Option<&str>; 6] {
+    let mut normalized = [None; 6];
+    for (slot, value) in normalized.iter_mut().zip(rule) {
+        if !value.is_empty() {
+            *slot = Some(value.as_str());
+        }
+    }
+    normalized
+}
+
+// Errors that leave the connection unusable. The server rolls back whatever
+// transaction was open on it. Postgres may still get a last error out first,
+// SQLSTATE class 08 or an administrator's 57P01 to 57P03.
+fn is_connection_lost(err: &SqlxError) -> bool {
+    match err {
+        SqlxError::Io(_)
+        | SqlxError::Tls(_)
+        | SqlxError::Protocol(_)
+        | SqlxError::WorkerCrashed => true,
+        SqlxError::Database(err) => err
+            .code()
+            .is_some_and(|code| code.starts_with("08") || code.starts_with("57P0")),
+        _ => false,
+    }
+}
+
+// Errors inside a transaction leave nothing behind. Serialization failures
+// and deadlocks become Error::SerializationFailure, lost connections
+// Error::TransactionAborted, both can be retried. Postgres reports the former
+// as SQLSTATE 40001 and 40P01, MySQL reports its deadlocks as 40001 too.
+pub(crate) fn map_transaction_error(err: SqlxError) -> CasbinError {
+    let is_serialization_failure = err
+        .as_database_error()
+        .and_then(|err| err.code())
+        .is_some_and(|code| code == "40001" || code == "40P01");
+
+    if is_serialization_failure {
+        CasbinError::from(Error::SerializationFailure(err))
+    } else if is_connection_lost(&err) {
+        CasbinError::from(Error::TransactionAborted(err))
+    } else {
+        CasbinError::from(Error::from(err))
+    }
+}
+
+// A connection lost during COMMIT may have taken the acknowledgement of a
+// successful commit with it, so the outcome is unknown. Errors the server
+// answers with still mean the transaction was rolled back.
+pub(crate) fn map_commit_error(err: SqlxError) -> CasbinError {
+    if is_connection_lost(&err) {
+        CasbinError::from(Error::CommitUnknown(err))
+    } else {
+        map_transaction_error(err)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InsertOutcome {
+    Inserted,
+    AlreadyExisted,
+    // Postgres and SQLite count a row changed by an upsert like an inserted
+    // one, only MySQL tells the two apart.
+    #[cfg(feature = "mysql")]
+    Updated,
+}
+
+// How an INSERT of `rows` rows went, by the backend's rows_affected. Rows
+// skipped by ON CONFLICT DO NOTHING or INSERT IGNORE are not counted.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub(crate) fn insert_outcome(result: &DbQueryResult, rows: usize) -> InsertOutcome {
+    if result.rows_affected() < rows as u64 {
+        InsertOutcome::AlreadyExisted
+    } else {
+        InsertOutcome::Inserted
+    }
+}
+
+// MySQL counts an inserted row once and a row changed by ON DUPLICATE KEY
+// UPDATE twice, ignored and unchanged rows not at all.
+#[cfg(feature = "mysql")]
+pub(crate) fn insert_outcome(result: &DbQueryResult, rows: usize) -> InsertOutcome {
+    use std::cmp::Ordering;
+
+    match result.rows_affected().cmp(&(rows as u64)) {
+        Ordering::Less => InsertOutcome::AlreadyExisted,
+        Ordering::Equal => InsertOutcome::Inserted,
+        Ordering::Greater => InsertOutcome::Updated,
+    }
+}
+
+// Unique violations become Error::AlreadyExists, callers that know the batch
+// fill in the index.
+fn map_insert_error(err: SqlxError) -> CasbinError {
+    let is_duplicate = err
+        .as_database_error()
+        .is_some_and(|err| err.is_unique_violation());
+
+    if is_duplicate {
+        CasbinError::from(Error::AlreadyExists { index: None })
+    } else {
+        map_transaction_error(err)
+    }
+}
+
+pub(crate) async fn acquire(conn: &ConnectionPool) -> Result<PoolConnection<Db>> {
+    conn.acquire().await.adapt()
+}
+
+// Transactions spanning several statements start at the configured isolation
+// level, or at the server's default without one. SQLite always runs them
+// serializably and ignores the level.
+#[cfg(feature = "postgres")]
+pub(crate) async fn begin(
+    conn: &mut DbConnection,
+    isolation_level: Option<IsolationLevel>,
+) -> Result<Transaction<'_, Db>> {
+    match isolation_level {
+        Some(level) => {
+            conn.begin_with(format!("BEGIN ISOLATION LEVEL {}", level.as_sql()))
+                .await
+        }
+        None => Connection::begin(conn).await,
+    }
+    .adapt()
+}
+
+// START TRANSACTION takes no isolation level, SET TRANSACTION applies it to
+// the next transaction on the connection only.
+#[cfg(feature = "mysql")]
+pub(crate) async fn begin(
+    conn: &mut DbConnection,
+    isolation_level: Option<IsolationLevel>,
+) -> Result<Transaction<'_, Db>> {
+    match isolation_level {
+        Some(level) => {
+            conn.begin_with(format!(
+                "SET TRANSACTION ISOLATION LEVEL {}; START TRANSACTION",
+                level.as_sql()
+            ))
+            .await
+        }
+        None => Connection::begin(conn).await,
+    }
+    .adapt()
+}
+
 #[cfg(feature = "sqlite")]
-pub(crate) async fn load_filtered_policy(
+pub(crate) async fn begin(
+    conn: &mut DbConnection,
+    _isolation_level: Option<IsolationLevel>,
+) -> Result<Transaction<'_, Db>> {
+    Connection::begin(conn).await.adapt()
+}
+
+// Statements that change the policy on their own run as they are, or in a
+// transaction that also records the change when the adapter keeps revisions
+// or an outbox. `changed` tells from the result whether there is anything to
+// record.
+async fn execute_mutation<'q>(
     conn: &ConnectionPool,
-    filter: &Filter<'_>,
-) -> Result<Vec<CasbinRule>> {
-    let (g_filter, p_filter) = filtered_where_values(filter);
-
-    let query_string = format!(
-        "SELECT id, ptype, v0, v1, v2, v3, v4, v5 from  casbin_rule WHERE (
-            ptype LIKE 'g%' AND v0 LIKE $1 AND v1 LIKE $2 AND v2 LIKE $3 AND v3 LIKE $4 AND v4 LIKE $5 AND v5 LIKE $6 )
-        OR (
-            ptype LIKE 'p%' AND v0 LIKE $7 AND v1 LIKE $8 AND v2 LIKE $9 AND v3 LIKE $10 AND v4 LIKE $11 AND v5 LIKE $12 );
-            ",
-        g_filter[0], g_filter[1], g_filter[2], g_filter[3], g_filter[4], g_filter[5],
-        p_filter[0], p_filter[1], p_filter[2], p_filter[3], p_filter[4], p_filter[5],
-    );
+    queries: &Queries,
+    query: Query<'q, Db, <Db as Database>::Arguments<'q>>,
+    changed: fn(&DbQueryResult) -> bool,
+    map_error: fn(SqlxError) -> CasbinError,
+    change: Change<'_>,
+) -> Result<DbQueryResult> {
+    if !records_changes(queries) {
+        return query.execute(conn).await.map_err(map_error);
+    }
+
+    let mut connection = acquire(conn).await?;
+    let mut transaction = begin(&mut connection, None).await?;
+    let result = query.execute(&mut *transaction).await.map_err(map_error)?;
+    if changed(&result) {
+        record_change(&mut transaction, queries, change).await?;
+    }
+    transaction.commit().await.map_err(map_commit_error)?;
+    Ok(result)
+}
+
+// A change to the policy, as recorded in the outbox.
+pub(crate) struct Change<'c> {
+    operation: Operation,
+    ptype: Option<&'c str>,
+    rules: ChangedValues<'c>,
+}
+
+enum ChangedValues<'c> {
+    Rules(Vec<(&'c str, [&'c str; 6])>),
+    Filtered(usize, Vec<&'c str>),
+    All,
+}
+
+impl<'c> Change<'c> {
+    fn rule(operation: Operation, ptype: &'c str, rule: [&'c str; 6]) -> Self {
+        Change {
+            operation,
+            ptype: Some(ptype),
+            rules: ChangedValues::Rules(vec![(ptype, rule)]),
+        }
+    }
+
+    fn rules(
+        operation: Operation,
+        ptype: Option<&'c str>,
+        rules: Vec<(&'c str, [&'c str; 6])>,
+    ) -> Self {
+        Change {
+            operation,
+            ptype,
+            rules: ChangedValues::Rules(rules),
+        }
+    }
+
+    fn filtered(
+        operation: Operation,
+        ptype: &'c str,
+        field_index: usize,
+        field_values: Vec<&'c str>,
+    ) -> Self {
+        Change {
+            operation,
+            ptype: Some(ptype),
+            rules: ChangedValues::Filtered(field_index, field_values),
+        }
+    }
+
+    fn all(operation: Operation, ptype: Option<&'c str>) -> Self {
+        Change {
+            operation,
+            ptype,
+            rules: ChangedValues::All,
+        }
+    }
+
+    // Rules are listed with their ptype and filters with their field index,
+    // both without trailing empty values, and changes to every rule
+    // of the ptype, or of any ptype without one, as `"all": true`.
+    fn payload(&self, tenant: Option<&str>) -> Value {
+        let mut payload = json!({ "ptype": self.ptype });
+        match &self.rules {
+            ChangedValues::Rules(rules) => {
+                payload["rules"] = rules
+                    .iter()
+                    .map(|(ptype, values)| json!({ "ptype": ptype, "values": trimmed(values) }))
+                    .collect();
+            }
+            ChangedValues::Filtered(field_index, field_values) => {
+                payload["field_index"] = json!(field_index);
+                payload["field_values"] = json!(trimmed(field_values));
+            }
+            ChangedValues::All => payload["all"] = json!(true),
+        }
+        if let Some(tenant) = tenant {
+            payload["tenant"] = json!(tenant);
+        }
+        payload
+    }
+}
+
+// Notifications name the operation and ptype along with the rule's values,
+// the rules' values for batches of one ptype, or the field index and values
+// of a filtered removal. Other changes, and those that would not fit in a
+// notification, are sent as `"bulk": true`.
+#[cfg(feature = "postgres")]
+impl Change<'_> {
+    fn notification(&self, tenant: Option<&str>) -> String {
+        let op = self.operation.as_str();
+        let mut notification = match &self.rules {
+            ChangedValues::Rules(rules) => match rules.first() {
+                Some((ptype, values)) if rules.iter().all(|(other, _)| other == ptype) => {
+                    let values = match self.operation {
+                        Operation::Add | Operation::Remove => json!(trimmed(values)),
+                        _ => rules
+                            .iter()
+                            .map(|(_, values)| json!(trimmed(values)))
+                            .collect(),
+                    };
+                    json!({ "op": op, "ptype": ptype, "values": values })
+                }
+                _ => json!({ "op": op, "bulk": true }),
+            },
+            ChangedValues::Filtered(field_index, field_values) => json!({
+                "op": op,
+                "ptype": self.ptype,
+                "field_index": field_index,
+                "values": trimmed(field_values),
+            }),
+            ChangedValues::All => json!({ "op": op, "bulk": true }),
+        };
+        if let Some(tenant) = tenant {
+            notification["tenant"] = json!(tenant);
+        }
+
+        let text = notification.to_string();
+        if text.len() <= MAX_NOTIFICATION || notification["bulk"] == json!(true) {
+            return text;
+        }
+        let mut bulk = json!({ "op": op, "bulk": true });
+        if let Some(tenant) = tenant {
+            bulk["tenant"] = json!(tenant);
+        }
+        bulk.to_string()
+    }
+}
+
+// Postgres refuses NOTIFY payloads of 8000 bytes or more.
+#[cfg(feature = "postgres")]
+const MAX_NOTIFICATION: usize = 7999;
+
+fn trimmed<'v>(values: &'v [&'v str]) -> &'v [&'v str] {
+    let len = values
+        .iter()
+        .rposition(|value| !value.is_empty())
+        .map_or(0, |i| i + 1);
+    &values[..len]
+}
+
+fn records_changes(queries: &Queries) -> bool {
+    #[cfg(feature = "postgres")]
+    if queries.options.notify.is_some() {
+        return true;
+    }
+    queries.bump_revision.is_some() || queries.insert_outbox.is_some()
+}
+
+// Bumps the revision, adds the change to the outbox and notifies the
+// listeners of it, whichever the adapter does. Postgres only delivers the
+// notification once the transaction commits.
+async fn record_change(
+    conn: &mut DbConnection,
+    queries: &Queries,
+    change: Change<'_>,
+) -> Result<()> {
+    if let Some(bump) = &queries.bump_revision {
+        sqlx::query(bump)
+            .execute(&mut *conn)
+            .await
+            .map_err(map_transaction_error)?;
+    }
+    if let Some(insert) = &queries.insert_outbox {
+        sqlx::query(insert)
+            .bind(change.operation.as_str())
+            .bind(Json(change.payload(queries.options.tenant.as_deref())))
+            .execute(&mut *conn)
+            .await
+            .map_err(map_transaction_error)?;
+    }
+    #[cfg(feature = "postgres")]
+    if let Some(channel) = &queries.options.notify {
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(&**channel)
+            .bind(change.notification(queries.options.tenant.as_deref()))
+            .execute(&mut *conn)
+            .await
+            .map_err(map_transaction_error)?;
+    }
+    Ok(())
+}
+
+fn insert_values<'q>(
+    queries: &'q Queries,
+    insert: &str,
+    chunk: &'q [NewCasbinRule<'q>],
+) -> QueryBuilder<'q, Db> {
+    let mut query = QueryBuilder::<Db>::new(format!(
+        "{} {} ( {}ptype, v0, v1, v2, v3, v4, v5{}{} ) ",
+        insert,
+        queries.table,
+        if queries.tenant().is_some() {
+            "tenant_id, "
+        } else {
+            ""
+        },
+        if queries.options.rule_hash {
+            ", rule_hash"
+        } else {
+            ""
+        },
+        if queries.actor().is_some() {
+            ", modified_by"
+        } else {
+            ""
+        }
+    ));
+    query.push_values(chunk, |mut row, rule| {
+        if let Some(tenant) = queries.tenant() {
+            row.push_bind(tenant);
+        }
+        row.push_bind(rule.ptype)
+            .push_bind(rule.v0)
+            .push_bind(rule.v1)
+            .push_bind(rule.v2)
+            .push_bind(rule.v3)
+            .push_bind(rule.v4)
+            .push_bind(rule.v5);
+        if queries.options.rule_hash {
+            row.push_bind(rule_hash(rule.ptype, rule.values()));
+        }
+        if let Some(actor) = queries.actor() {
+            row.push_bind(actor);
+        }
+    });
+    query
+}
 
-    let casbin_rule: Vec<CasbinRule> = sqlx::query_as(&query_string)
+// Rules are pulled from the iterator one batch at a time, so callers can
+// convert them lazily. Returns how many were inserted.
+async fn insert_rules<'r, I>(
+    conn: &mut DbConnection,
+    queries: &Queries,
+    rules: I,
+    batch_size: usize,
+) -> Result<u64>
+where
+    I: IntoIterator<Item = NewCasbinRule<'r>>,
+    I::IntoIter: Send,
+{
+    let mut rules = rules.into_iter();
+    let mut chunk = Vec::with_capacity(batch_size);
+    let mut inserted = 0;
+
+    loop {
+        chunk.clear();
+        chunk.extend(rules.by_ref().take(batch_size));
+        if chunk.is_empty() {
+            break;
+        }
+
+        insert_values(queries, "INSERT INTO", &chunk)
+            .build()
+            .persistent(false)
+            .execute(&mut *conn)
+            .await
+            .and_then(|n| match insert_outcome(&n, chunk.len()) {
+                InsertOutcome::Inserted => Ok(()),
+                _ => Err(SqlxError::RowNotFound),
+            })
+            .map_err(map_insert_error)?;
+        inserted += chunk.len() as u64;
+    }
+    Ok(inserted)
+}
+
+// With the enabled flag, the model's rules that are stored disabled stay as
+// they are instead of being inserted a second time.
+async fn insert_saved_rules<'r, I>(
+    conn: &mut DbConnection,
+    queries: &Queries,
+    rules: I,
+    batch_size: usize,
+) -> Result<u64>
+where
+    I: IntoIterator<Item = NewCasbinRule<'r>>,
+    I::IntoIter: Send,
+{
+    if !queries.options.enabled_flag {
+        return insert_rules(conn, queries, rules, batch_size).await;
+    }
+
+    let disabled = disabled_rules(&mut *conn, queries).await?;
+    let disabled: HashSet<NewCasbinRule> = disabled
+        .iter()
+        .map(|rule| NewCasbinRule {
+            ptype: &rule.ptype,
+            v0: &rule.v0,
+            v1: &rule.v1,
+            v2: &rule.v2,
+            v3: &rule.v3,
+            v4: &rule.v4,
+            v5: &rule.v5,
+        })
+        .collect();
+    let rules: Vec<NewCasbinRule> = rules
+        .into_iter()
+        .filter(|rule| !disabled.contains(rule))
+        .collect();
+    insert_rules(conn, queries, rules, batch_size).await
+}
+
+// Descriptions are not part of the model, so save_policy reads them before
+// clearing and writes them back to the rules it stores again. Those of rules
+// missing from the model go with them.
+async fn saved_descriptions(conn: &mut DbConnection, queries: &Queries) -> Result<Vec<CasbinRule>> {
+    if !queries.options.descriptions {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query_as(&queries.described)
+        .bind_tenant(queries)
         .fetch_all(conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+        .map_err(map_transaction_error)
+}
 
-    Ok(casbin_rule)
+async fn restore_descriptions(
+    conn: &mut DbConnection,
+    queries: &Queries,
+    rules: Vec<CasbinRule>,
+) -> Result<()> {
+    for rule in rules {
+        if let Some(description) = &rule.description {
+            let values = [
+                rule.v0.as_str(),
+                &rule.v1,
+                &rule.v2,
+                &rule.v3,
+                &rule.v4,
+                &rule.v5,
+            ];
+            describe_rule(&mut *conn, queries, &rule.ptype, values, description)
+                .await
+                .map_err(map_transaction_error)?;
+        }
+    }
+    Ok(())
 }
 
-#[cfg(feature = "mysql")]
-pub(crate) async fn load_filtered_policy(
+// A table save_policy rewrites in full, or only in the rules of `section`
+// when the table holds both.
+pub(crate) struct SavedTable<'q, I> {
+    pub queries: &'q Queries,
+    pub section: Option<Section>,
+    pub rules: I,
+}
+
+impl<I> SavedTable<'_, I> {
+    fn clear(&self) -> &str {
+        match self.section {
+            Some(section) => self.queries.section_clear(section),
+            None => &self.queries.save_clear,
+        }
+    }
+}
+
+// The tables are rewritten in one transaction, and their locks taken in the
+// order given, which is the same for every save.
+#[cfg(feature = "postgres")]
+pub(crate) async fn save_policy<'r, I>(
     conn: &ConnectionPool,
-    filter: &Filter<'_>,
-) -> Result<Vec<CasbinRule>> {
-    let (g_filter, p_filter) = filtered_where_values(filter);
-
-    let query_string = format!(
-        "SELECT id, ptype, v0, v1, v2, v3, v4, v5 from  casbin_rule WHERE (
-            ptype LIKE 'g%' AND v0 LIKE ? AND v1 LIKE ? AND v2 LIKE ? AND v3 LIKE ? AND v4 LIKE ? AND v5 LIKE ? )
-        OR (
-            ptype LIKE 'p%' AND v0 LIKE ? AND v1 LIKE ? AND v2 LIKE ? AND v3 LIKE ? AND v4 LIKE ? AND v5 LIKE ? );
-            ",
-        g_filter[0], g_filter[1], g_filter[2], g_filter[3], g_filter[4], g_filter[5],
-        p_filter[0], p_filter[1], p_filter[2], p_filter[3], p_filter[4], p_filter[5],
-    );
+    tables: Vec<SavedTable<'_, I>>,
+    batch_size: usize,
+    isolation_level: Option<IsolationLevel>,
+    lock: bool,
+) -> Result<()>
+where
+    I: IntoIterator<Item = NewCasbinRule<'r>>,
+    I::IntoIter: Send,
+{
+    let mut connection = acquire(conn).await?;
+    let mut transaction = begin(&mut connection, isolation_level).await?;
+
+    // The locks are released with the transaction.
+    if lock {
+        for table in &tables {
+            sqlx::query("SELECT pg_advisory_xact_lock($1)")
+                .bind(table.queries.save_lock)
+                .execute(&mut *transaction)
+                .await
+                .map_err(map_transaction_error)?;
+        }
+    }
 
-    let casbin_rule: Vec<CasbinRule> = sqlx::query_as(&query_string)
-        .fetch_all(conn)
+    for table in tables {
+        let queries = table.queries;
+        let descriptions = saved_descriptions(&mut transaction, queries).await?;
+        if table.section.is_none() {
+            truncate_table(&mut transaction, queries, &queries.save_clear).await?;
+        } else {
+            sqlx::query(table.clear())
+                .bind_tenant(queries)
+                .execute(&mut *transaction)
+                .await
+                .map_err(map_transaction_error)?;
+        }
+
+        insert_saved_rules(&mut transaction, queries, table.rules, batch_size).await?;
+        restore_descriptions(&mut transaction, queries, descriptions).await?;
+        let change = Change::all(Operation::Save, None);
+        record_change(&mut transaction, queries, change).await?;
+    }
+    transaction.commit().await.map_err(map_commit_error)?;
+    Ok(())
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+async fn replace_rules<'r, I>(
+    conn: &mut DbConnection,
+    table: SavedTable<'_, I>,
+    batch_size: usize,
+) -> Result<()>
+where
+    I: IntoIterator<Item = NewCasbinRule<'r>>,
+    I::IntoIter: Send,
+{
+    let queries = table.queries;
+    let descriptions = saved_descriptions(&mut *conn, queries).await?;
+    sqlx::query(table.clear())
+        .bind_tenant(queries)
+        .execute(&mut *conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+        .map_err(map_transaction_error)?;
 
-    Ok(casbin_rule)
+    insert_saved_rules(&mut *conn, queries, table.rules, batch_size).await?;
+    restore_descriptions(&mut *conn, queries, descriptions).await?;
+    record_change(conn, queries, Change::all(Operation::Save, None)).await
 }
 
-fn normalize_casbin_rule(mut rule: Vec<String>) -> Vec<String> {
-    rule.resize(6, String::new());
-    rule
+// BEGIN IMMEDIATE takes the write lock up front, so a concurrent save waits
+// for it instead of failing to upgrade a read lock.
+#[cfg(feature = "sqlite")]
+pub(crate) async fn save_policy<'r, I>(
+    conn: &ConnectionPool,
+    tables: Vec<SavedTable<'_, I>>,
+    batch_size: usize,
+    isolation_level: Option<IsolationLevel>,
+    lock: bool,
+) -> Result<()>
+where
+    I: IntoIterator<Item = NewCasbinRule<'r>>,
+    I::IntoIter: Send,
+{
+    let mut connection = acquire(conn).await?;
+    let mut transaction = if lock {
+        connection
+            .begin_with("BEGIN IMMEDIATE")
+            .await
+            .map_err(map_transaction_error)?
+    } else {
+        begin(&mut connection, isolation_level).await?
+    };
+
+    for table in tables {
+        replace_rules(&mut transaction, table, batch_size).await?;
+    }
+    transaction.commit().await.map_err(map_commit_error)?;
+    Ok(())
 }
 
-fn normalize_casbin_rule_option(rule: Vec<String>) -> Vec<Option<String>> {
-    let mut rule_with_option = rule
-        .iter()
-        .map(|x| match x.is_empty() {
-            true => None,
-            false => Some(x.clone()),
-        })
-        .collect::<Vec<Option<String>>>();
-    rule_with_option.resize(6, None);
-    rule_with_option
+// GET_LOCK belongs to the session rather than the transaction, so it is
+// released by hand once the transaction is over. A connection that fails to
+// release it is closed instead of going back to the pool, which ends the
+// session and the lock with it.
+#[cfg(feature = "mysql")]
+pub(crate) async fn save_policy<'r, I>(
+    conn: &ConnectionPool,
+    tables: Vec<SavedTable<'_, I>>,
+    batch_size: usize,
+    isolation_level: Option<IsolationLevel>,
+    lock: bool,
+) -> Result<()>
+where
+    I: IntoIterator<Item = NewCasbinRule<'r>>,
+    I::IntoIter: Send,
+{
+    let mut connection = acquire(conn).await?;
+    let locked: Vec<&Queries> = if lock {
+        tables.iter().map(|table| table.queries).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut result = Ok(());
+    for queries in &locked {
+        result = sqlx::query("SELECT GET_LOCK(?, -1)")
+            .bind(&*queries.save_lock)
+            .execute(&mut *connection)
+            .await
+            .map(|_| ())
+            .adapt();
+        if result.is_err() {
+            break;
+        }
+    }
+
+    if result.is_ok() {
+        result = async {
+            let mut transaction = begin(&mut connection, isolation_level).await?;
+            for table in tables {
+                replace_rules(&mut transaction, table, batch_size).await?;
+            }
+            transaction.commit().await.map_err(map_commit_error)
+        }
+        .await;
+    }
+
+    // Releasing a lock that is not held does no harm.
+    for queries in &locked {
+        let released = sqlx::query("SELECT RELEASE_LOCK(?)")
+            .bind(&*queries.save_lock)
+            .execute(&mut *connection)
+            .await;
+        if let Err(err) = released {
+            connection.close_on_drop();
+            result?;
+            return Err(err).adapt();
+        }
+    }
+
+    result
 }
 
-#[cfg(feature = "postgres")]
-pub(crate) async fn save_policy(
-    conn: &ConnectionPool,
-    table_name: &str,
-    rules: Vec<NewCasbinRule<'_>>,
-) -> Result<()> {
-    let mut transaction = conn
-        .begin()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-
-    sqlx::query(&format!("DELETE FROM {}", table_name))
-        .execute(&mut *transaction)
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+// Checked at build time for the default table with `compile-checked`.
+fn insert_query<'q>(
+    queries: &'q Queries,
+    rule: NewCasbinRule<'q>,
+) -> Query<'q, Db, <Db as Database>::Arguments<'q>> {
+    #[cfg(all(feature = "compile-checked", feature = "postgres"))]
+    if queries.is_static() {
+        return checked::insert(rule);
+    }
 
-    for rule in rules {
-        sqlx::query(&format!(
-            "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-                 VALUES ( $1, $2, $3, $4, $5, $6, $7 )",
-            table_name
-        ))
+    sqlx::query(&queries.insert)
+        .bind_tenant(queries)
         .bind(rule.ptype)
         .bind(rule.v0)
         .bind(rule.v1)
@@ -793,46 +2676,81 @@ pub(crate) async fn save_policy(
         .bind(rule.v3)
         .bind(rule.v4)
         .bind(rule.v5)
-        .execute(&mut *transaction)
-        .await
-        .and_then(|n| {
-            if PgQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
-            } else {
-                Err(SqlxError::RowNotFound)
-            }
-        })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    }
-    transaction
-        .commit()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(())
+        .bind_rule_hash(queries, rule.ptype, rule.values())
+        .bind_actor(queries)
 }
 
-#[cfg(feature = "sqlite")]
-pub(crate) async fn save_policy(
+pub(crate) async fn add_policy(
     conn: &ConnectionPool,
-    table_name: &str,
-    rules: Vec<NewCasbinRule<'_>>,
-) -> Result<()> {
-    let mut transaction = conn
-        .begin()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+    queries: &Queries,
+    rule: NewCasbinRule<'_>,
+) -> Result<bool> {
+    let query = insert_query(queries, rule);
+
+    execute_mutation(
+        conn,
+        queries,
+        query,
+        |n| insert_outcome(n, 1) == InsertOutcome::Inserted,
+        map_insert_error,
+        Change::rule(Operation::Add, rule.ptype, rule.values()),
+    )
+    .await
+    .map(|n| insert_outcome(&n, 1) == InsertOutcome::Inserted)
+}
 
-    sqlx::query(&format!("DELETE FROM {}", table_name))
-        .execute(&mut *transaction)
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+// Like add_policy, with a rule already stored left as it is and reported as
+// not added instead of failing.
+pub(crate) async fn add_policy_if_absent(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    rule: NewCasbinRule<'_>,
+) -> Result<bool> {
+    let (insert, on_duplicate) = INSERT_IGNORING_DUPLICATES;
+    let rules = [rule];
+    let mut query = insert_values(queries, insert, &rules);
+    query.push(on_duplicate);
+
+    execute_mutation(
+        conn,
+        queries,
+        query.build().persistent(false),
+        |n| n.rows_affected() > 0,
+        map_insert_error,
+        Change::rule(Operation::Add, rule.ptype, rule.values()),
+    )
+    .await
+    .map(|n| n.rows_affected() > 0)
+}
 
-    for rule in rules {
-        sqlx::query(&format!(
-            "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-                 VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7 )",
-            table_name
-        ))
+// The stored rules of `ptype` whose v0 is `v0`, in id order.
+pub(crate) async fn rules_for_v0(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    ptype: &str,
+    v0: &str,
+) -> Result<Vec<CasbinRule>> {
+    let statement = &queries.remove_filtered[0];
+    let mut select = sqlx::query_as(&statement.select)
+        .bind_tenant(queries)
+        .bind(ptype)
+        .bind(Some(v0));
+    for _ in 1..statement.values {
+        select = select.bind(None::<&str>);
+    }
+    let mut rules: Vec<CasbinRule> = select.fetch_all(conn).await.adapt()?;
+    rules.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(rules)
+}
+
+pub(crate) async fn add_policy_with_metadata(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    rule: NewCasbinRule<'_>,
+    metadata: &Value,
+) -> Result<bool> {
+    let query = sqlx::query(&queries.insert_with_metadata)
+        .bind_tenant(queries)
         .bind(rule.ptype)
         .bind(rule.v0)
         .bind(rule.v1)
@@ -840,46 +2758,30 @@ pub(crate) async fn save_policy(
         .bind(rule.v3)
         .bind(rule.v4)
         .bind(rule.v5)
-        .execute(&mut *transaction)
-        .await
-        .and_then(|n| {
-            if SqliteQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
-            } else {
-                Err(SqlxError::RowNotFound)
-            }
-        })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    }
-    transaction
-        .commit()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(())
+        .bind_rule_hash(queries, rule.ptype, rule.values())
+        .bind(Json(metadata))
+        .bind_actor(queries);
+
+    execute_mutation(
+        conn,
+        queries,
+        query,
+        |n| insert_outcome(n, 1) == InsertOutcome::Inserted,
+        map_insert_error,
+        Change::rule(Operation::Add, rule.ptype, rule.values()),
+    )
+    .await
+    .map(|n| insert_outcome(&n, 1) == InsertOutcome::Inserted)
 }
 
-#[cfg(feature = "mysql")]
-pub(crate) async fn save_policy(
+pub(crate) async fn add_policy_with_enabled(
     conn: &ConnectionPool,
-    table_name: &str,
-    rules: Vec<NewCasbinRule<'_>>,
-) -> Result<()> {
-    let mut transaction = conn
-        .begin()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-
-    sqlx::query(&format!("DELETE FROM {}", table_name))
-        .execute(&mut *transaction)
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-
-    for rule in rules {
-        sqlx::query(&format!(
-            "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-                 VALUES ( ?, ?, ?, ?, ?, ?, ? )",
-            table_name
-        ))
+    queries: &Queries,
+    rule: NewCasbinRule<'_>,
+    enabled: bool,
+) -> Result<bool> {
+    let query = sqlx::query(&queries.insert_with_enabled)
+        .bind_tenant(queries)
         .bind(rule.ptype)
         .bind(rule.v0)
         .bind(rule.v1)
@@ -887,245 +2789,491 @@ pub(crate) async fn save_policy(
         .bind(rule.v3)
         .bind(rule.v4)
         .bind(rule.v5)
-        .execute(&mut *transaction)
-        .await
-        .and_then(|n| {
-            if MySqlQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
-            } else {
-                Err(SqlxError::RowNotFound)
-            }
-        })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    }
-    transaction
-        .commit()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(())
+        .bind_rule_hash(queries, rule.ptype, rule.values())
+        .bind(enabled)
+        .bind_actor(queries);
+
+    execute_mutation(
+        conn,
+        queries,
+        query,
+        |n| insert_outcome(n, 1) == InsertOutcome::Inserted,
+        map_insert_error,
+        Change::rule(Operation::Add, rule.ptype, rule.values()),
+    )
+    .await
+    .map(|n| insert_outcome(&n, 1) == InsertOutcome::Inserted)
 }
 
-#[cfg(feature = "postgres")]
-pub(crate) async fn add_policy(
+pub(crate) async fn add_policy_with_expiry(
     conn: &ConnectionPool,
-    table_name: &str,
+    queries: &Queries,
     rule: NewCasbinRule<'_>,
+    expires_at: i64,
 ) -> Result<bool> {
-    sqlx::query(&format!(
-        "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-             VALUES ( $1, $2, $3, $4, $5, $6, $7 )",
-        table_name
-    ))
-    .bind(rule.ptype)
-    .bind(rule.v0)
-    .bind(rule.v1)
-    .bind(rule.v2)
-    .bind(rule.v3)
-    .bind(rule.v4)
-    .bind(rule.v5)
-    .execute(conn)
+    let query = sqlx::query(&queries.insert_with_expiry)
+        .bind_tenant(queries)
+        .bind(rule.ptype)
+        .bind(rule.v0)
+        .bind(rule.v1)
+        .bind(rule.v2)
+        .bind(rule.v3)
+        .bind(rule.v4)
+        .bind(rule.v5)
+        .bind_rule_hash(queries, rule.ptype, rule.values())
+        .bind(expires_at)
+        .bind_actor(queries);
+
+    execute_mutation(
+        conn,
+        queries,
+        query,
+        |n| insert_outcome(n, 1) == InsertOutcome::Inserted,
+        map_insert_error,
+        Change::rule(Operation::Add, rule.ptype, rule.values()),
+    )
     .await
-    .map(|n| PgQueryResult::rows_affected(&n) == 1)
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .map(|n| insert_outcome(&n, 1) == InsertOutcome::Inserted)
 }
 
-#[cfg(feature = "sqlite")]
-pub(crate) async fn add_policy(
+// False when the rule is missing or already in the requested state.
+pub(crate) async fn set_policy_enabled(
     conn: &ConnectionPool,
-    table_name: &str,
-    rule: NewCasbinRule<'_>,
+    queries: &Queries,
+    pt: &str,
+    rule: &[String],
+    enabled: bool,
 ) -> Result<bool> {
-    sqlx::query(&format!(
-        "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-             VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7 )",
-        table_name
-    ))
-    .bind(rule.ptype)
-    .bind(rule.v0)
-    .bind(rule.v1)
-    .bind(rule.v2)
-    .bind(rule.v3)
-    .bind(rule.v4)
-    .bind(rule.v5)
-    .execute(conn)
+    let rule = normalize_casbin_rule(rule);
+    let sql = if enabled {
+        &queries.enable_rule
+    } else {
+        &queries.disable_rule
+    };
+    let query = sqlx::query(sql);
+    // MySQL binds in the order of the text, where the actor comes first.
+    #[cfg(feature = "mysql")]
+    let query = query.bind_actor(queries);
+    let query = query
+        .bind_tenant(queries)
+        .bind(pt)
+        .bind(rule[0])
+        .bind(rule[1])
+        .bind(rule[2])
+        .bind(rule[3])
+        .bind(rule[4])
+        .bind(rule[5]);
+    #[cfg(any(feature = "postgres", feature = "sqlite"))]
+    let query = query.bind_actor(queries);
+
+    let operation = if enabled {
+        Operation::Add
+    } else {
+        Operation::Remove
+    };
+    execute_mutation(
+        conn,
+        queries,
+        query,
+        |n| n.rows_affected() > 0,
+        |err| CasbinError::from(Error::from(err)),
+        Change::rule(operation, pt, rule),
+    )
     .await
-    .map(|n| SqliteQueryResult::rows_affected(&n) == 1)
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .map(|n| n.rows_affected() > 0)
 }
 
-#[cfg(feature = "mysql")]
-pub(crate) async fn add_policy(
+async fn describe_rule(
+    conn: &mut DbConnection,
+    queries: &Queries,
+    pt: &str,
+    rule: [&str; 6],
+    description: &str,
+) -> std::result::Result<DbQueryResult, SqlxError> {
+    let query = sqlx::query(&queries.set_description);
+    // MySQL binds in the order of the text, where the update's own values
+    // come first.
+    #[cfg(feature = "mysql")]
+    let query = query.bind(description).bind_actor(queries);
+    let query = query
+        .bind_tenant(queries)
+        .bind(pt)
+        .bind(rule[0])
+        .bind(rule[1])
+        .bind(rule[2])
+        .bind(rule[3])
+        .bind(rule[4])
+        .bind(rule[5]);
+    #[cfg(any(feature = "postgres", feature = "sqlite"))]
+    let query = query.bind(description).bind_actor(queries);
+
+    query.execute(conn).await
+}
+
+// Descriptions are not part of the policy and leave the revision alone.
+pub(crate) async fn set_policy_description(
     conn: &ConnectionPool,
-    table_name: &str,
-    rule: NewCasbinRule<'_>,
+    queries: &Queries,
+    pt: &str,
+    rule: &[String],
+    description: &str,
 ) -> Result<bool> {
-    sqlx::query(&format!(
-        "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-             VALUES ( ?, ?, ?, ?, ?, ?, ? )",
-        table_name
-    ))
-    .bind(rule.ptype)
-    .bind(rule.v0)
-    .bind(rule.v1)
-    .bind(rule.v2)
-    .bind(rule.v3)
-    .bind(rule.v4)
-    .bind(rule.v5)
-    .execute(conn)
+    let mut connection = acquire(conn).await?;
+    describe_rule(
+        &mut connection,
+        queries,
+        pt,
+        normalize_casbin_rule(rule),
+        description,
+    )
     .await
-    .map(|n| MySqlQueryResult::rows_affected(&n) == 1)
-    .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+    .map(|n| n.rows_affected() > 0)
+    .adapt()
 }
 
-#[cfg(feature = "postgres")]
-pub(crate) async fn clear_policy(conn: &ConnectionPool, table_name: &str) -> Result<()> {
-    sqlx::query(&format!("DELETE FROM {}", table_name))
-        .execute(conn)
+pub(crate) async fn policy_metadata(
+    conn: &ConnectionPool,
+    queries: &Queries,
+    pt: &str,
+    rule: &[String],
+) -> Result<Option<Value>> {
+    let rule = normalize_casbin_rule(rule);
+    sqlx::query_scalar::<_, Option<Json<Value>>>(&queries.metadata)
+        .bind_tenant(queries)
+        .bind(pt)
+        .bind(rule[0])
+        .bind(rule[1])
+        .bind(rule[2])
+        .bind(rule[3])
+        .bind(rule[4])
+        .bind(rule[5])
+        .fetch_optional(conn)
         .await
-        .map(|_| ())
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+        .map(|metadata| metadata.flatten().map(|Json(metadata)| metadata))
+        .adapt()
 }
 
-#[cfg(feature = "sqlite")]
-pub(crate) async fn clear_policy(conn: &ConnectionPool, table_name: &str) -> Result<()> {
-    sqlx::query(&format!("DELETE FROM {}", table_name))
-        .execute(conn)
-        .await
-        .map(|_| ())
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+// TRUNCATE avoids the dead tuples a full DELETE leaves behind, but it needs
+// the TRUNCATE privilege and is refused for tables referenced by foreign keys,
+// so any database-side failure falls back to DELETE. Running it under a
+// savepoint keeps a failed TRUNCATE from aborting an enclosing transaction.
+#[cfg(feature = "postgres")]
+async fn truncate_table(conn: &mut PgConnection, queries: &Queries, clear: &str) -> Result<()> {
+    let truncate = match &queries.truncate {
+        Some(truncate) => truncate,
+        None => {
+            return sqlx::query(clear)
+                .bind_tenant(queries)
+                .execute(&mut *conn)
+                .await
+                .map(|_| ())
+                .map_err(map_transaction_error)
+        }
+    };
+
+    let mut savepoint = conn.begin().await.adapt()?;
+
+    match sqlx::query(truncate).execute(&mut *savepoint).await {
+        Ok(_) => savepoint.commit().await.adapt(),
+        Err(SqlxError::Database(_)) => {
+            savepoint.rollback().await.adapt()?;
+
+            sqlx::query(clear)
+                .bind_tenant(queries)
+                .execute(&mut *conn)
+                .await
+                .map(|_| ())
+                .map_err(map_transaction_error)
+        }
+        Err(err) => Err(map_transaction_error(err)),
+    }
 }
 
-#[cfg(feature = "mysql")]
-pub(crate) async fn clear_policy(conn: &ConnectionPool, table_name: &str) -> Result<()> {
-    sqlx::query(&format!("DELETE FROM {}", table_name))
-        .execute(conn)
-        .await
-        .map(|_| ())
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))
+#[cfg(feature = "postgres")]
+pub(crate) async fn clear_policy(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let mut conn = conn.acquire().await.adapt()?;
+
+    if !records_changes(queries) {
+        return truncate_table(&mut conn, queries, &queries.clear).await;
+    }
+
+    let mut transaction = begin(&mut conn, None).await?;
+    truncate_table(&mut transaction, queries, &queries.clear).await?;
+    record_change(
+        &mut transaction,
+        queries,
+        Change::all(Operation::Clear, None),
+    )
+    .await?;
+    transaction.commit().await.map_err(map_commit_error)
+}
+
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+pub(crate) async fn clear_policy(conn: &ConnectionPool, queries: &Queries) -> Result<()> {
+    let query = sqlx::query(&queries.clear).bind_tenant(queries);
+
+    execute_mutation(
+        conn,
+        queries,
+        query,
+        |_| true,
+        |err| CasbinError::from(Error::from(err)),
+        Change::all(Operation::Clear, None),
+    )
+    .await
+    .map(|_| ())
 }
 
+// Clears the tables of a split adapter in one transaction, recording the
+// change in each.
+pub(crate) async fn clear_tables(conn: &ConnectionPool, tables: &[&Queries]) -> Result<()> {
+    let mut connection = acquire(conn).await?;
+    let mut transaction = begin(&mut connection, None).await?;
+    for queries in tables {
+        #[cfg(feature = "postgres")]
+        truncate_table(&mut transaction, queries, &queries.clear).await?;
+        #[cfg(any(feature = "sqlite", feature = "mysql"))]
+        sqlx::query(&queries.clear)
+            .bind_tenant(queries)
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_transaction_error)?;
+        record_change(
+            &mut transaction,
+            queries,
+            Change::all(Operation::Clear, None),
+        )
+        .await?;
+    }
+    transaction.commit().await.map_err(map_commit_error)
+}
+
+// Rules already stored are skipped rather than failing the import.
 #[cfg(feature = "postgres")]
-pub(crate) async fn add_policies(
+const INSERT_IGNORING_DUPLICATES: (&str, &str) = ("INSERT INTO", " ON CONFLICT DO NOTHING");
+
+#[cfg(feature = "mysql")]
+const INSERT_IGNORING_DUPLICATES: (&str, &str) = ("INSERT IGNORE INTO", "");
+
+#[cfg(feature = "sqlite")]
+const INSERT_IGNORING_DUPLICATES: (&str, &str) = ("INSERT OR IGNORE INTO", "");
+
+// The rule columns of another adapter's table, which may hold NULLs.
+pub(crate) type ForeignRule = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+pub(crate) async fn load_foreign_rules(
     conn: &ConnectionPool,
     table_name: &str,
-    rules: Vec<NewCasbinRule<'_>>,
-) -> Result<bool> {
-    let mut transaction = conn
-        .begin()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+) -> Result<Vec<ForeignRule>> {
+    sqlx::query_as(&format!(
+        "SELECT ptype, v0, v1, v2, v3, v4, v5 FROM {} ORDER BY id",
+        quote(table_name)
+    ))
+    .fetch_all(conn)
+    .await
+    .adapt()
+}
 
-    for rule in rules {
-        sqlx::query(&format!(
-            "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-                 VALUES ( $1, $2, $3, $4, $5, $6, $7 )",
-            table_name
-        ))
-        .bind(rule.ptype)
-        .bind(rule.v0)
-        .bind(rule.v1)
-        .bind(rule.v2)
-        .bind(rule.v3)
-        .bind(rule.v4)
-        .bind(rule.v5)
-        .execute(&mut *transaction)
-        .await
-        .and_then(|n| {
-            if PgQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
+// Imports into several tables in one transaction, each ptype in batches of
+// its own so that its count is known. Rules already stored are skipped, or
+// with `strict` fail the import. Returns how many rules of each ptype were
+// new.
+pub(crate) async fn import_rules_by_ptype(
+    conn: &ConnectionPool,
+    tables: Vec<(&Queries, Vec<NewCasbinRule<'_>>)>,
+    strict: bool,
+    batch_size: usize,
+    isolation_level: Option<IsolationLevel>,
+) -> Result<BTreeMap<String, u64>> {
+    let (insert, on_duplicate) = INSERT_IGNORING_DUPLICATES;
+    let mut connection = acquire(conn).await?;
+    let mut transaction = begin(&mut connection, isolation_level).await?;
+
+    let mut imported = BTreeMap::new();
+    for (queries, rules) in tables {
+        let mut by_ptype: BTreeMap<&str, Vec<NewCasbinRule>> = BTreeMap::new();
+        for rule in rules {
+            by_ptype.entry(rule.ptype).or_default().push(rule);
+        }
+
+        let mut table_imported = 0;
+        for (ptype, rules) in by_ptype {
+            let mut ptype_imported = 0;
+            if strict {
+                ptype_imported = rules.len() as u64;
+                insert_rules(&mut transaction, queries, rules, batch_size.max(1)).await?;
             } else {
-                Err(SqlxError::RowNotFound)
+                for chunk in rules.chunks(batch_size.max(1)) {
+                    let mut query = insert_values(queries, insert, chunk);
+                    query.push(on_duplicate);
+                    ptype_imported += query
+                        .build()
+                        .persistent(false)
+                        .execute(&mut *transaction)
+                        .await
+                        .map_err(map_transaction_error)?
+                        .rows_affected();
+                }
             }
-        })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+            table_imported += ptype_imported;
+            imported.insert(ptype.to_owned(), ptype_imported);
+        }
+
+        if table_imported > 0 {
+            let change = Change::all(Operation::AddMany, None);
+            record_change(&mut transaction, queries, change).await?;
+        }
     }
-    transaction
-        .commit()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(true)
+
+    transaction.commit().await.map_err(map_commit_error)?;
+    Ok(imported)
 }
 
-#[cfg(feature = "sqlite")]
 pub(crate) async fn add_policies(
     conn: &ConnectionPool,
-    table_name: &str,
-    rules: Vec<NewCasbinRule<'_>>,
+    queries: &Queries,
+    mut rules: Vec<NewCasbinRule<'_>>,
+    batch_size: usize,
+    isolation_level: Option<IsolationLevel>,
 ) -> Result<bool> {
-    let mut transaction = conn
-        .begin()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+    if rules.len() <= 1 {
+        return match rules.pop() {
+            Some(rule) => add_policy(conn, queries, rule).await,
+            None => Ok(true),
+        };
+    }
 
-    for rule in rules {
-        sqlx::query(&format!(
-            "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-                 VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7 )",
-            table_name
-        ))
-        .bind(rule.ptype)
-        .bind(rule.v0)
-        .bind(rule.v1)
-        .bind(rule.v2)
-        .bind(rule.v3)
-        .bind(rule.v4)
-        .bind(rule.v5)
-        .execute(&mut *transaction)
+    let mut connection = acquire(conn).await?;
+    let mut transaction = begin(&mut connection, isolation_level).await?;
+
+    let change = Change::rules(
+        Operation::AddMany,
+        None,
+        rules
+            .iter()
+            .map(|rule| (rule.ptype, rule.values()))
+            .collect(),
+    );
+    insert_rules(&mut transaction, queries, rules, batch_size).await?;
+    record_change(&mut transaction, queries, change).await?;
+    transaction.commit().await.map_err(map_commit_error)?;
+    Ok(true)
+}
+
+// Dry runs execute the statements of a change on a transaction the caller
+// rolls back, see `SqlxAdapter::dry_run`. They count the rows the change
+// would touch and record nothing in the revision or the outbox.
+pub(crate) async fn dry_add_policy(
+    conn: &mut DbConnection,
+    queries: &Queries,
+    rule: NewCasbinRule<'_>,
+) -> Result<u64> {
+    insert_query(queries, rule)
+        .execute(conn)
         .await
-        .and_then(|n| {
-            if SqliteQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
-            } else {
-                Err(SqlxError::RowNotFound)
-            }
+        .map(|n| match insert_outcome(&n, 1) {
+            InsertOutcome::AlreadyExisted => 0,
+            _ => 1,
         })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    }
-    transaction
-        .commit()
-        .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(true)
+        .map_err(map_insert_error)
 }
 
-#[cfg(feature = "mysql")]
-pub(crate) async fn add_policies(
-    conn: &ConnectionPool,
-    table_name: &str,
+pub(crate) async fn dry_add_policies(
+    conn: &mut DbConnection,
+    queries: &Queries,
     rules: Vec<NewCasbinRule<'_>>,
-) -> Result<bool> {
-    let mut transaction = conn
-        .begin()
+    batch_size: usize,
+) -> Result<u64> {
+    insert_rules(conn, queries, rules, batch_size).await
+}
+
+// Like remove_policies, a batch with a missing rule removes nothing. Each
+// rule comes with the queries of its table and its ptype.
+pub(crate) async fn dry_remove_policies(
+    conn: &mut DbConnection,
+    rules: &[(&Queries, &str, &[String])],
+) -> Result<u64> {
+    let mut removed = 0;
+    for &(queries, pt, rule) in rules {
+        let rule = normalize_casbin_rule(rule);
+        let n = remove_query(queries, pt, rule)
+            .execute(&mut *conn)
+            .await
+            .map_err(map_transaction_error)?
+            .rows_affected();
+        if n == 0 && rules.len() > 1 {
+            return Ok(0);
+        }
+        removed += n;
+    }
+    Ok(removed)
+}
+
+pub(crate) async fn dry_remove_filtered_policy(
+    conn: &mut DbConnection,
+    queries: &Queries,
+    pt: &str,
+    field_index: usize,
+    field_values: &[String],
+) -> Result<(u64, Vec<CasbinRule>)> {
+    let field_values = normalize_casbin_rule_option(field_values);
+    let statement = &queries.remove_filtered[field_index];
+
+    let mut select = sqlx::query_as(&statement.select)
+        .bind_tenant(queries)
+        .bind(pt);
+    let mut remove = sqlx::query(&statement.sql).bind_tenant(queries).bind(pt);
+    for &value in field_values.iter().take(statement.values) {
+        select = select.bind(value);
+        remove = remove.bind(value);
+    }
+
+    let rules = select
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(map_transaction_error)?;
+    let removed = remove
+        .execute(&mut *conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
+        .map_err(map_transaction_error)?
+        .rows_affected();
+    Ok((removed, rules))
+}
 
-    for rule in rules {
-        sqlx::query(&format!(
-            "INSERT INTO {} ( ptype, v0, v1, v2, v3, v4, v5 )
-                 VALUES ( ?, ?, ?, ?, ?, ?, ? )",
-            table_name
-        ))
-        .bind(rule.ptype)
-        .bind(rule.v0)
-        .bind(rule.v1)
-        .bind(rule.v2)
-        .bind(rule.v3)
-        .bind(rule.v4)
-        .bind(rule.v5)
-        .execute(&mut *transaction)
+pub(crate) async fn dry_clear_policy(conn: &mut DbConnection, queries: &Queries) -> Result<u64> {
+    sqlx::query(&queries.clear)
+        .bind_tenant(queries)
+        .execute(conn)
         .await
-        .and_then(|n| {
-            if MySqlQueryResult::rows_affected(&n) == 1 {
-                Ok(true)
-            } else {
-                Err(SqlxError::RowNotFound)
-            }
-        })
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    }
-    transaction
-        .commit()
+        .map(|n| n.rows_affected())
+        .map_err(map_transaction_error)
+}
+
+// The rows save_policy would clear from the table and insert into it.
+pub(crate) async fn dry_save_policy<'r, I>(
+    conn: &mut DbConnection,
+    table: SavedTable<'_, I>,
+    batch_size: usize,
+) -> Result<(u64, u64)>
+where
+    I: IntoIterator<Item = NewCasbinRule<'r>>,
+    I::IntoIter: Send,
+{
+    let queries = table.queries;
+    let cleared = sqlx::query(table.clear())
+        .bind_tenant(queries)
+        .execute(&mut *conn)
         .await
-        .map_err(|err| CasbinError::from(AdapterError(Box::new(Error::SqlxError(err)))))?;
-    Ok(true)
+        .map_err(map_transaction_error)?
+        .rows_affected();
+    let inserted = insert_saved_rules(conn, queries, table.rules, batch_size).await?;
+    Ok((cleared, inserted))
 }
@@ -0,0 +1,280 @@
+//! Administers the casbin rules of a table from the command line, through
+//! the adapter's public API. Exits with 0 on success, 1 on errors, 2 on
+//! usage errors, 3 when the rule to remove is not stored and 4 when
+//! `check-schema` finds the table unusable.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use sqlx_adapter::casbin::{Adapter, Result};
+use sqlx_adapter::{CasbinRuleRecord, ImportMode, Section, SqlxAdapter, SqlxAdapterBuilder};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const NOT_FOUND: u8 = 3;
+const SCHEMA_MISMATCH: u8 = 4;
+
+// Rows fetched per page by `list` and `count`.
+const PAGE_SIZE: u64 = 1000;
+
+#[derive(Parser)]
+#[command(version, about = "Administers casbin rules stored by sqlx-adapter")]
+struct Cli {
+    /// The database, as a sqlx connection URL.
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: String,
+    /// The table holding the rules, created if it does not exist yet by
+    /// every command but `check-schema`.
+    #[arg(long, global = true, default_value = "casbin_rule")]
+    table: String,
+    /// Prints JSON instead of tables.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lists the stored rules.
+    List,
+    /// Stores a rule.
+    Add { ptype: String, values: Vec<String> },
+    /// Removes a rule.
+    Remove { ptype: String, values: Vec<String> },
+    /// Removes the rules whose values from FIELD_INDEX on match VALUES,
+    /// where an empty value matches any.
+    RemoveFiltered {
+        ptype: String,
+        field_index: usize,
+        values: Vec<String>,
+    },
+    /// Stores the rules of a policy file in casbin's CSV format.
+    ImportCsv {
+        /// The file to read, standard input if left out.
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value = "merge")]
+        mode: Mode,
+    },
+    /// Writes the stored rules in casbin's CSV format.
+    ExportCsv {
+        /// The file to write, standard output if left out.
+        file: Option<PathBuf>,
+    },
+    /// Counts the stored rules by ptype.
+    Count,
+    /// Compares the table with the columns the adapter needs.
+    CheckSchema,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mode {
+    Replace,
+    Merge,
+    Strict,
+}
+
+impl From<Mode> for ImportMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Replace => ImportMode::Replace,
+            Mode::Merge => ImportMode::Merge,
+            Mode::Strict => ImportMode::Strict,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(code) => ExitCode::from(code),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<u8> {
+    let connected = SqlxAdapterBuilder::new(&cli.database_url, 1)
+        .table_name(&cli.table)
+        .connect()
+        .await?;
+    // check-schema looks at the table as it is, without creating it.
+    let mut adapter = match cli.command {
+        Command::CheckSchema => connected.assume_table_exists(),
+        _ => connected.ensure_table().await?,
+    };
+
+    match cli.command {
+        Command::List => {
+            let rules = all_rules(&adapter).await?;
+            if cli.json {
+                println!("{}", json!(rules));
+            } else {
+                print_table(&rules);
+            }
+        }
+        Command::Add { ptype, values } => {
+            let added = adapter
+                .add_policy(Section::of(&ptype).as_str(), &ptype, values)
+                .await?;
+            report(cli.json, "added", added, "not stored");
+        }
+        Command::Remove { ptype, values } => {
+            let removed = adapter
+                .remove_policy(Section::of(&ptype).as_str(), &ptype, values)
+                .await?;
+            report(cli.json, "removed", removed, "not found");
+            if !removed {
+                return Ok(NOT_FOUND);
+            }
+        }
+        Command::RemoveFiltered {
+            ptype,
+            field_index,
+            values,
+        } => {
+            let removed = adapter
+                .remove_filtered_policy(Section::of(&ptype).as_str(), &ptype, field_index, values)
+                .await?;
+            report(cli.json, "removed", removed, "not found");
+            if !removed {
+                return Ok(NOT_FOUND);
+            }
+        }
+        Command::ImportCsv { file, mode } => {
+            let mut text = Vec::new();
+            match file {
+                Some(path) => text = std::fs::read(path)?,
+                None => {
+                    std::io::stdin().read_to_end(&mut text)?;
+                }
+            }
+            let summary = adapter.import_csv(&text[..], mode.into()).await?;
+            if cli.json {
+                println!(
+                    "{}",
+                    json!({ "imported": summary.imported(), "skipped": summary.skipped() })
+                );
+            } else {
+                println!(
+                    "imported {} rules, skipped {}",
+                    summary.total(),
+                    summary.skipped()
+                );
+            }
+        }
+        Command::ExportCsv { file } => {
+            let mut text = Vec::new();
+            let exported = adapter.export_csv(&mut text).await?;
+            match file {
+                Some(path) => {
+                    std::fs::write(path, text)?;
+                    eprintln!("exported {} rules", exported);
+                }
+                None => std::io::stdout().write_all(&text)?,
+            }
+        }
+        Command::Count => {
+            let mut counts = BTreeMap::new();
+            for record in all_rules(&adapter).await? {
+                let (ptype, _) = record.rule.into_parts();
+                *counts.entry(ptype).or_insert(0u64) += 1;
+            }
+            if cli.json {
+                println!("{}", json!(counts));
+            } else {
+                for (ptype, count) in &counts {
+                    println!("{:<8} {}", ptype, count);
+                }
+                println!("{:<8} {}", "total", counts.values().sum::<u64>());
+            }
+        }
+        Command::CheckSchema => {
+            let report = adapter.validate_schema().await?;
+            let incompatible = report
+                .incompatible
+                .iter()
+                .map(|column| format!("{} ({})", column.column, column.data_type))
+                .collect::<Vec<_>>();
+            if cli.json {
+                println!(
+                    "{}",
+                    json!({
+                        "compatible": report.is_compatible(),
+                        "missing": report.missing,
+                        "incompatible": incompatible,
+                        "nullable": report.nullable,
+                        "extra": report.extra,
+                    })
+                );
+            } else {
+                println!("compatible    {}", report.is_compatible());
+                println!("missing       {}", report.missing.join(", "));
+                println!("incompatible  {}", incompatible.join(", "));
+                println!("nullable      {}", report.nullable.join(", "));
+                println!("extra         {}", report.extra.join(", "));
+            }
+            if !report.is_compatible() {
+                return Ok(SCHEMA_MISMATCH);
+            }
+        }
+    }
+    Ok(0)
+}
+
+async fn all_rules(adapter: &SqlxAdapter) -> Result<Vec<CasbinRuleRecord>> {
+    let mut rules = Vec::new();
+    loop {
+        let page = adapter.list_rules(rules.len() as u64, PAGE_SIZE).await?;
+        let last = (page.len() as u64) < PAGE_SIZE;
+        rules.extend(page);
+        if last {
+            return Ok(rules);
+        }
+    }
+}
+
+fn report(json: bool, done: &str, changed: bool, otherwise: &str) {
+    if json {
+        println!("{}", json!({ done: changed }));
+    } else if changed {
+        println!("{}", done);
+    } else {
+        println!("{}", otherwise);
+    }
+}
+
+fn print_table(rules: &[CasbinRuleRecord]) {
+    let rows = rules
+        .iter()
+        .map(|record| {
+            let mut row = vec![record.id.as_str(), record.rule.ptype()];
+            row.extend(record.rule.values().iter().map(String::as_str));
+            row
+        })
+        .collect::<Vec<_>>();
+    let header = ["id", "ptype", "v0", "v1", "v2", "v3", "v4", "v5"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.len());
+        }
+    }
+
+    let print_row = |row: &[&str]| {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect::<Vec<_>>();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(&header);
+    for row in &rows {
+        print_row(row);
+    }
+}
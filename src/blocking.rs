@@ -0,0 +1,234 @@
+//! A synchronous adapter for applications without an async runtime of their
+//! own.
+//!
+//! ```no_run
+//! use sqlx_adapter::blocking::SqlxAdapter;
+//! use sqlx_adapter::PolicyRule;
+//!
+//! let adapter = SqlxAdapter::new("postgres://localhost/casbin", 8)?;
+//! let rule = PolicyRule::new("p", vec!["alice".into(), "data1".into(), "read".into()])?;
+//! adapter.add_policy(rule)?;
+//! let rules = adapter.load_policy()?;
+//! # Ok::<(), sqlx_adapter::casbin::Error>(())
+//! ```
+
+use casbin::{Adapter, Error as CasbinError, Result};
+use futures_util::TryStreamExt;
+use std::future::Future;
+
+use crate::{
+    connect_options::IntoConnectOptions, error::Error, policy_csv::ImportMode,
+    policy_rule::PolicyRule, rule_record::CasbinRuleRecord, section::Section,
+};
+
+/// `SqlxAdapter` with its calls run to completion on the calling thread, on
+/// a runtime of its own. It fails with `Error::AsyncContext` when created or
+/// called on a thread that already runs an async runtime, which it would
+/// stall.
+pub struct SqlxAdapter {
+    // Dropped before the runtime its connections were opened on.
+    adapter: crate::SqlxAdapter,
+    executor: Executor,
+}
+
+impl SqlxAdapter {
+    pub fn new<U: IntoConnectOptions>(url: U, pool_size: u32) -> Result<Self> {
+        let executor = Executor::new()?;
+        let adapter = executor.block_on(crate::SqlxAdapter::new(url, pool_size))?;
+        Ok(Self { adapter, executor })
+    }
+
+    pub fn new_with_table_name<U: IntoConnectOptions, T: Into<String>>(
+        url: U,
+        pool_size: u32,
+        table_name: T,
+    ) -> Result<Self> {
+        let executor = Executor::new()?;
+        let adapter = executor.block_on(crate::SqlxAdapter::new_with_table_name(
+            url, pool_size, table_name,
+        ))?;
+        Ok(Self { adapter, executor })
+    }
+
+    pub fn table_name(&self) -> &str {
+        self.adapter.table_name()
+    }
+
+    /// The stored rules, ordered by ptype and by id within each.
+    pub fn load_policy(&self) -> Result<Vec<PolicyRule>> {
+        self.executor.block_on(
+            self.adapter
+                .exported_rules()
+                .map_ok(|(ptype, values)| PolicyRule::stored(ptype, values))
+                .try_collect(),
+        )
+    }
+
+    /// Replaces the stored rules with `rules` in one transaction.
+    pub fn save_policy(&self, rules: Vec<PolicyRule>) -> Result<()> {
+        let policies = rules.into_iter().map(PolicyRule::into_parts).collect();
+        self.executor.block_on(async {
+            self.adapter
+                .import_policies(policies, ImportMode::Replace, |_, err| err)
+                .await
+                .map(drop)
+        })
+    }
+
+    pub fn add_policy(&self, rule: PolicyRule) -> Result<bool> {
+        let (ptype, rule) = rule.into_parts();
+        let sec = Section::of(&ptype).as_str();
+        self.executor
+            .block_on(self.casbin_adapter().add_policy(sec, &ptype, rule))
+    }
+
+    /// Adds the rules of each ptype all or nothing, one ptype after the
+    /// other.
+    pub fn add_policies(&self, rules: Vec<PolicyRule>) -> Result<bool> {
+        let mut adapter = self.casbin_adapter();
+        self.executor.block_on(async move {
+            for (ptype, rules) in by_ptype(rules) {
+                let sec = Section::of(&ptype).as_str();
+                if !adapter.add_policies(sec, &ptype, rules).await? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        })
+    }
+
+    pub fn remove_policy(&self, rule: PolicyRule) -> Result<bool> {
+        let (ptype, rule) = rule.into_parts();
+        let sec = Section::of(&ptype).as_str();
+        self.executor
+            .block_on(self.casbin_adapter().remove_policy(sec, &ptype, rule))
+    }
+
+    /// Removes the rules of each ptype all or nothing, one ptype after the
+    /// other, and stops at the first ptype with a rule that is not stored.
+    pub fn remove_policies(&self, rules: Vec<PolicyRule>) -> Result<bool> {
+        let mut adapter = self.casbin_adapter();
+        self.executor.block_on(async move {
+            for (ptype, rules) in by_ptype(rules) {
+                let sec = Section::of(&ptype).as_str();
+                if !adapter.remove_policies(sec, &ptype, rules).await? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        })
+    }
+
+    /// Removes the rules whose values from `field_index` on match those of
+    /// `filter`, where an empty value matches any.
+    pub fn remove_filtered_policy(&self, field_index: usize, filter: PolicyRule) -> Result<bool> {
+        let (ptype, field_values) = filter.into_parts();
+        let sec = Section::of(&ptype).as_str();
+        self.executor
+            .block_on(self.casbin_adapter().remove_filtered_policy(
+                sec,
+                &ptype,
+                field_index,
+                field_values,
+            ))
+    }
+
+    pub fn clear_policy(&self) -> Result<()> {
+        self.executor.block_on(self.casbin_adapter().clear_policy())
+    }
+
+    /// See `SqlxAdapter::has_policy`.
+    pub fn has_policy(&self, rule: &PolicyRule) -> Result<bool> {
+        self.executor.block_on(self.adapter.has_policy(rule))
+    }
+
+    /// See `SqlxAdapter::list_rules`.
+    pub fn list_rules(&self, offset: u64, limit: u64) -> Result<Vec<CasbinRuleRecord>> {
+        self.executor
+            .block_on(self.adapter.list_rules(offset, limit))
+    }
+
+    /// See `SqlxAdapter::assign_role`.
+    pub fn assign_role(&self, user: &str, role: &str, domain: Option<&str>) -> Result<bool> {
+        self.executor
+            .block_on(self.adapter.assign_role(user, role, domain))
+    }
+
+    /// See `SqlxAdapter::revoke_role`.
+    pub fn revoke_role(&self, user: &str, role: &str, domain: Option<&str>) -> Result<bool> {
+        self.executor
+            .block_on(self.adapter.revoke_role(user, role, domain))
+    }
+
+    /// See `SqlxAdapter::list_roles_for_user`.
+    pub fn list_roles_for_user(&self, user: &str, domain: Option<&str>) -> Result<Vec<String>> {
+        self.executor
+            .block_on(self.adapter.list_roles_for_user(user, domain))
+    }
+
+    // casbin's Adapter calls take the adapter mutably, so they run on a clone
+    // of it, which shares its pool and state.
+    fn casbin_adapter(&self) -> crate::SqlxAdapter {
+        self.adapter.clone()
+    }
+}
+
+// The values of `rules` by ptype, the ptypes in the order they first come.
+fn by_ptype(rules: Vec<PolicyRule>) -> Vec<(String, Vec<Vec<String>>)> {
+    let mut grouped: Vec<(String, Vec<Vec<String>>)> = Vec::new();
+    for rule in rules {
+        let (ptype, values) = rule.into_parts();
+        match grouped.iter_mut().find(|(seen, _)| *seen == ptype) {
+            Some((_, group)) => group.push(values),
+            None => grouped.push((ptype, vec![values])),
+        }
+    }
+    grouped
+}
+
+// Runs the adapter's futures on the calling thread, on a current-thread tokio
+// runtime or on async-std's global executor.
+struct Executor {
+    #[cfg(feature = "runtime-tokio")]
+    runtime: tokio::runtime::Runtime,
+}
+
+impl Executor {
+    fn new() -> Result<Self> {
+        check_context()?;
+
+        #[cfg(feature = "runtime-tokio")]
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| CasbinError::from(Error::Io(err)))?;
+
+        Ok(Self {
+            #[cfg(feature = "runtime-tokio")]
+            runtime,
+        })
+    }
+
+    fn block_on<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        check_context()?;
+
+        #[cfg(feature = "runtime-tokio")]
+        return self.runtime.block_on(future);
+
+        #[cfg(all(feature = "runtime-async-std", not(feature = "runtime-tokio")))]
+        return async_std::task::block_on(future);
+    }
+}
+
+// Blocking within a task would stall its runtime, and tokio panics on it.
+fn check_context() -> Result<()> {
+    #[cfg(feature = "runtime-tokio")]
+    let in_runtime = tokio::runtime::Handle::try_current().is_ok();
+    #[cfg(all(feature = "runtime-async-std", not(feature = "runtime-tokio")))]
+    let in_runtime = async_std::task::try_current().is_some();
+
+    if in_runtime {
+        return Err(CasbinError::from(Error::AsyncContext));
+    }
+    Ok(())
+}
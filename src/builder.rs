@@ -0,0 +1,139 @@
+use casbin::Result;
+
+use crate::{
+    actions as adapter,
+    connect_options::IntoConnectOptions,
+    error::ResultExt,
+    id_type::IdType,
+    queries::{canonical_table_name, Queries, QueryOptions},
+    SqlxAdapter,
+};
+
+/// Connects without touching the schema, for setups that create or migrate
+/// the rule table in a separate step. `connect` yields an
+/// `UninitializedAdapter`, which only becomes a `SqlxAdapter` once the
+/// table is taken care of.
+///
+/// ```no_run
+/// # async fn run() -> casbin::Result<()> {
+/// use sqlx_adapter::SqlxAdapterBuilder;
+///
+/// let pending = SqlxAdapterBuilder::new("postgres://localhost/casbin", 8)
+///     .table_name("casbin_rule")
+///     .connect()
+///     .await?;
+/// // The table is created by the deployment's own migrations.
+/// let adapter = pending.assume_table_exists();
+/// # Ok(())
+/// # }
+/// ```
+pub struct SqlxAdapterBuilder<U> {
+    url: U,
+    pool_size: u32,
+    table_name: String,
+    id_type: IdType,
+}
+
+impl<U: IntoConnectOptions> SqlxAdapterBuilder<U> {
+    pub fn new(url: U, pool_size: u32) -> Self {
+        Self {
+            url,
+            pool_size,
+            table_name: "casbin_rule".to_owned(),
+            id_type: IdType::BigInt,
+        }
+    }
+
+    pub fn table_name<T: Into<String>>(mut self, table_name: T) -> Self {
+        self.table_name = table_name.into();
+        self
+    }
+
+    /// The primary key `ensure_table` creates the table with, and the one
+    /// the adapter expects of an existing table.
+    pub fn id_type(mut self, id_type: IdType) -> Self {
+        self.id_type = id_type;
+        self
+    }
+
+    /// Opens the pool after checking the table name. The table is neither
+    /// created nor looked at.
+    pub async fn connect(self) -> Result<UninitializedAdapter> {
+        let table_name = canonical_table_name(self.table_name).adapt()?;
+        let pool = SqlxAdapter::open_pool(self.url, self.pool_size).await?;
+        let queries = Queries::new_with_options(
+            &table_name,
+            QueryOptions {
+                id_type: self.id_type,
+                ..QueryOptions::default()
+            },
+        );
+        Ok(UninitializedAdapter { pool, queries })
+    }
+}
+
+/// A connected adapter whose table may not exist yet. It offers no policy
+/// methods until `ensure_table` or `assume_table_exists` turn it into a
+/// `SqlxAdapter`.
+///
+/// ```compile_fail
+/// # async fn run() -> casbin::Result<()> {
+/// use sqlx_adapter::casbin::Adapter;
+/// use sqlx_adapter::SqlxAdapterBuilder;
+///
+/// let mut pending = SqlxAdapterBuilder::new("postgres://localhost/casbin", 8)
+///     .connect()
+///     .await?;
+/// pending.add_policy("p", "p", vec!["alice".to_owned()]).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # async fn run() -> casbin::Result<()> {
+/// use sqlx_adapter::casbin::{prelude::*, Result};
+/// use sqlx_adapter::SqlxAdapterBuilder;
+///
+/// let pending = SqlxAdapterBuilder::new("postgres://localhost/casbin", 8)
+///     .connect()
+///     .await?;
+/// let m = DefaultModel::from_file("examples/rbac_model.conf").await?;
+/// let e = Enforcer::new(m, pending).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct UninitializedAdapter {
+    pool: adapter::ConnectionPool,
+    queries: Queries,
+}
+
+impl UninitializedAdapter {
+    pub fn pool(&self) -> &adapter::ConnectionPool {
+        &self.pool
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.queries.table_name
+    }
+
+    /// Creates the table if it does not exist yet, as the one-shot
+    /// constructors do.
+    pub async fn ensure_table(self) -> Result<SqlxAdapter> {
+        adapter::new_with_table_name(&self.pool, &self.queries).await?;
+        Ok(SqlxAdapter::from_parts(self.pool, self.queries))
+    }
+
+    /// Creates or upgrades the table as `SqlxAdapter::run_migrations`
+    /// does.
+    pub async fn run_migrations(self) -> Result<SqlxAdapter> {
+        adapter::run_migrations(&self.pool, &self.queries).await?;
+        Ok(SqlxAdapter::from_parts(self.pool, self.queries))
+    }
+
+    /// Trusts that the table has been created, for example by
+    /// `run_migrations` or the statements of `migrations`. Calls fail with
+    /// `Error::NotFound` when it has not.
+    pub fn assume_table_exists(self) -> SqlxAdapter {
+        SqlxAdapter::from_parts(self.pool, self.queries)
+    }
+}
@@ -0,0 +1,59 @@
+// The statements of the default `casbin_rule` table with the default
+// options, checked against its schema at build time. `cargo sqlx prepare
+// -- --features compile-checked` refreshes the metadata in `.sqlx` that
+// offline builds check them with. Every other table goes through the
+// statements of `Queries`, which these have to match.
+use crate::models::{CasbinRule, NewCasbinRule, RuleId};
+use sqlx::{postgres::PgArguments, query::Query, PgPool, Postgres};
+
+pub(crate) fn insert<'q>(rule: NewCasbinRule<'q>) -> Query<'q, Postgres, PgArguments> {
+    sqlx::query!(
+        r#"INSERT INTO "casbin_rule" ( ptype, v0, v1, v2, v3, v4, v5 ) VALUES ( $1, $2, $3, $4, $5, $6, $7 )"#,
+        rule.ptype,
+        rule.v0,
+        rule.v1,
+        rule.v2,
+        rule.v3,
+        rule.v4,
+        rule.v5
+    )
+}
+
+pub(crate) fn remove<'q>(pt: &'q str, rule: [&'q str; 6]) -> Query<'q, Postgres, PgArguments> {
+    sqlx::query!(
+        r#"DELETE FROM "casbin_rule" WHERE ptype = $1 AND v0 = $2 AND v1 = $3 AND v2 = $4 AND v3 = $5 AND v4 = $6 AND v5 = $7"#,
+        pt,
+        rule[0],
+        rule[1],
+        rule[2],
+        rule[3],
+        rule[4],
+        rule[5]
+    )
+}
+
+pub(crate) async fn load(conn: &PgPool) -> sqlx::Result<Vec<CasbinRule>> {
+    let rows = sqlx::query!(
+        r#"SELECT CAST(id AS BIGINT) AS "id!", ptype, v0, v1, v2, v3, v4, v5 FROM "casbin_rule" ORDER BY id"#
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| CasbinRule {
+            id: RuleId::BigInt(row.id),
+            ptype: row.ptype,
+            v0: row.v0,
+            v1: row.v1,
+            v2: row.v2,
+            v3: row.v3,
+            v4: row.v4,
+            v5: row.v5,
+            created_at: None,
+            updated_at: None,
+            modified_by: None,
+            description: None,
+        })
+        .collect())
+}
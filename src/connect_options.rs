@@ -0,0 +1,46 @@
+use crate::{actions::ConnectOptions, error::ResultExt};
+use casbin::Result;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// What the constructors of `SqlxAdapter` connect with: a database URL, or
+/// the backend's connect options, such as `PgConnectOptions`, which keep
+/// settings a URL cannot hold.
+pub trait IntoConnectOptions: sealed::Sealed {
+    #[doc(hidden)]
+    fn into_connect_options(self) -> Result<ConnectOptions>;
+}
+
+impl sealed::Sealed for &str {}
+
+impl IntoConnectOptions for &str {
+    fn into_connect_options(self) -> Result<ConnectOptions> {
+        self.parse().adapt()
+    }
+}
+
+impl sealed::Sealed for String {}
+
+impl IntoConnectOptions for String {
+    fn into_connect_options(self) -> Result<ConnectOptions> {
+        self.as_str().into_connect_options()
+    }
+}
+
+impl sealed::Sealed for &String {}
+
+impl IntoConnectOptions for &String {
+    fn into_connect_options(self) -> Result<ConnectOptions> {
+        self.as_str().into_connect_options()
+    }
+}
+
+impl sealed::Sealed for ConnectOptions {}
+
+impl IntoConnectOptions for ConnectOptions {
+    fn into_connect_options(self) -> Result<ConnectOptions> {
+        Ok(self)
+    }
+}
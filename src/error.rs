@@ -1,9 +1,96 @@
+use casbin::{error::AdapterError, Error as CasbinError};
 use sqlx::error::Error as SqlxError;
-use std::{error::Error as StdError, fmt};
+use std::{error::Error as StdError, fmt, io};
 
+/// The errors of the adapter, found by downcasting the `AdapterError` of a
+/// casbin error. Database errors the adapter tells apart get their own
+/// variant, `SqlxError` holds the others. Those of the casbin `Adapter`
+/// calls come wrapped in `Context`.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
+    /// Any other error of the database or of the connection to it.
     SqlxError(SqlxError),
+    /// A table the call needs, usually the rule table, does not exist.
+    NotFound(SqlxError),
+    /// A value longer than its column allows. SQLite stores values of any
+    /// length.
+    ValueTooLong(SqlxError),
+    /// The pool was closed, by `close` or because the adapter has no
+    /// database, see `SqlxAdapter::new_with_store`.
+    PoolClosed,
+    /// No connection was free within the pool's acquire timeout.
+    Timeout,
+    /// A table name that is empty, holds a NUL character, or is longer than
+    /// the 63 bytes Postgres and MySQL take.
+    InvalidTableName { name: String },
+    /// The transaction was rolled back after a serialization failure or a
+    /// deadlock with a concurrent writer. Nothing was changed and the call
+    /// can be retried.
+    SerializationFailure(SqlxError),
+    /// The connection was lost before the transaction was committed, so the
+    /// server rolled it back. Nothing was changed and the call can be
+    /// retried.
+    TransactionAborted(SqlxError),
+    /// The connection was lost while committing, so the transaction may or
+    /// may not have been applied. Reload or inspect the table to find out.
+    CommitUnknown(SqlxError),
+    /// A rule without a single non-empty value.
+    EmptyRule,
+    /// `save_policy` was handed a model without rules, see
+    /// `SqlxAdapter::with_allow_empty_save`.
+    RefusingToClearTable,
+    /// `save_policy` was called after a filtered load, when the model holds
+    /// only part of the stored rules. A full `load_policy` lifts this.
+    RefusingToSaveFilteredPolicy,
+    /// A rule with more values than the table has columns for.
+    TooManyFields { expected: usize, got: usize },
+    /// The value at `index` holds a control character, see
+    /// `SqlxAdapter::with_reject_control_characters`.
+    ControlCharacter { index: usize },
+    /// The rule is already stored. `index` is the position of the offending
+    /// rule in `add_policies` or `save_policy`, when it could be found.
+    AlreadyExists { index: Option<usize> },
+    /// The table made by a custom DDL lacks `column`, or has it with a type
+    /// the adapter cannot read or write.
+    SchemaMismatch {
+        column: &'static str,
+        found: Option<String>,
+    },
+    /// A rule passed to `SqlxAdapter::save_section_policy` that belongs to
+    /// the other section.
+    WrongSection { ptype: String },
+    /// The citext extension is not installed, and creating it failed, see
+    /// `SqlxAdapter::make_case_insensitive`.
+    CitextUnavailable(SqlxError),
+    /// An index name that is not letters, digits and underscores of at most
+    /// 63 bytes, or an index without columns.
+    InvalidIndex { name: String },
+    /// A line of the policy file passed to `SqlxAdapter::import_csv` that
+    /// could not be read as a rule, counted from 1.
+    InvalidCsv { line: usize, reason: String },
+    /// An entry of the document passed to `SqlxAdapter::import_json` that is
+    /// not a rule, named like `p[3]`, or None when the document itself is
+    /// not an object of ptypes.
+    InvalidJson {
+        entry: Option<String>,
+        reason: String,
+    },
+    /// Reading or writing a policy file failed.
+    Io(io::Error),
+    /// A `blocking::SqlxAdapter` was created or called on a thread that runs
+    /// an async runtime.
+    AsyncContext,
+    /// `source` ended the adapter call `operation`, such as `add_policies`
+    /// or `set_policy_enabled`, on `table`. `index` is the position of the rule the
+    /// batch failed at, when it is known. Its `source` is that of the error
+    /// it wraps, `Error::inner` returns the error itself.
+    Context {
+        operation: &'static str,
+        table: String,
+        index: Option<usize>,
+        source: Box<Error>,
+    },
 }
 
 impl fmt::Display for Error {
@@ -12,6 +99,93 @@ impl fmt::Display for Error {
 
         match self {
             SqlxError(sqlx_error) => sqlx_error.fmt(f),
+            NotFound(sqlx_error) => write!(f, "table does not exist: {}", sqlx_error),
+            ValueTooLong(sqlx_error) => {
+                write!(f, "value is too long for its column: {}", sqlx_error)
+            }
+            PoolClosed => f.write_str("connection pool is closed"),
+            Timeout => f.write_str("timed out waiting for a connection"),
+            InvalidTableName { name } => write!(
+                f,
+                "table name {:?} must be 1 to 63 bytes without NUL characters",
+                name
+            ),
+            SerializationFailure(sqlx_error) => {
+                write!(f, "transaction can be retried: {}", sqlx_error)
+            }
+            TransactionAborted(sqlx_error) => {
+                write!(f, "transaction was rolled back: {}", sqlx_error)
+            }
+            CommitUnknown(sqlx_error) => {
+                write!(f, "transaction may or may not be committed: {}", sqlx_error)
+            }
+            EmptyRule => f.write_str("rule has no non-empty value"),
+            RefusingToClearTable => f.write_str("refusing to save a model without rules"),
+            RefusingToSaveFilteredPolicy => f.write_str(
+                "refusing to save a filtered policy, load the full policy before saving",
+            ),
+            TooManyFields { expected, got } => write!(
+                f,
+                "rule has {} values but at most {} can be stored",
+                got, expected
+            ),
+            ControlCharacter { index } => {
+                write!(f, "value {} holds a control character", index)
+            }
+            AlreadyExists { index: Some(index) } => {
+                write!(f, "rule {} is already stored", index)
+            }
+            AlreadyExists { index: None } => f.write_str("rule is already stored"),
+            SchemaMismatch {
+                column,
+                found: None,
+            } => write!(f, "table has no {} column", column),
+            SchemaMismatch {
+                column,
+                found: Some(data_type),
+            } => write!(
+                f,
+                "column {} has type {}, which the adapter cannot use",
+                column, data_type
+            ),
+            WrongSection { ptype } => {
+                write!(f, "ptype {:?} is not part of the saved section", ptype)
+            }
+            CitextUnavailable(sqlx_error) => write!(
+                f,
+                "citext extension is not installed and could not be created: {}",
+                sqlx_error
+            ),
+            InvalidIndex { name } => write!(
+                f,
+                "index {:?} needs a name of letters, digits and underscores and at least one column",
+                name
+            ),
+            InvalidCsv { line, reason } => write!(f, "policy line {}: {}", line, reason),
+            InvalidJson {
+                entry: Some(entry),
+                reason,
+            } => write!(f, "policy entry {}: {}", entry, reason),
+            InvalidJson {
+                entry: None,
+                reason,
+            } => write!(f, "policy document: {}", reason),
+            Io(io_error) => io_error.fmt(f),
+            AsyncContext => {
+                f.write_str("blocking adapter cannot be used within an async runtime")
+            }
+            Context {
+                operation,
+                table,
+                index,
+                source,
+            } => {
+                write!(f, "{} on table {:?}", operation, table)?;
+                if let Some(index) = index {
+                    write!(f, " at rule {}", index)?;
+                }
+                write!(f, ": {}", source)
+            }
         }
     }
 }
@@ -21,13 +195,112 @@ impl StdError for Error {
         use Error::*;
 
         match self {
-            SqlxError(sqlx_err) => Some(sqlx_err),
+            SqlxError(sqlx_err)
+            | NotFound(sqlx_err)
+            | ValueTooLong(sqlx_err)
+            | SerializationFailure(sqlx_err)
+            | TransactionAborted(sqlx_err)
+            | CommitUnknown(sqlx_err)
+            | CitextUnavailable(sqlx_err) => Some(sqlx_err),
+            Io(io_err) => Some(io_err),
+            Context { source, .. } => source.source(),
+            PoolClosed
+            | Timeout
+            | InvalidTableName { .. }
+            | EmptyRule
+            | RefusingToClearTable
+            | RefusingToSaveFilteredPolicy
+            | TooManyFields { .. }
+            | ControlCharacter { .. }
+            | AlreadyExists { .. }
+            | SchemaMismatch { .. }
+            | WrongSection { .. }
+            | InvalidIndex { .. }
+            | InvalidCsv { .. }
+            | InvalidJson { .. }
+            | AsyncContext => None,
         }
     }
 }
 
+impl Error {
+    /// The error without the context of `Error::Context`, for matching on
+    /// what went wrong.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.inner(),
+            err => err,
+        }
+    }
+
+    /// The casbin error carrying this one, as the adapter returns it.
+    pub fn into_casbin_error(self) -> CasbinError {
+        CasbinError::from(AdapterError(Box::new(self)))
+    }
+
+    /// Whether the failed call left nothing behind and can be retried as is.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.inner(),
+            Error::SerializationFailure(_) | Error::TransactionAborted(_)
+        )
+    }
+}
+
+// Postgres and MySQL report the SQLSTATE, SQLite only its generic error code
+// for a missing table.
+#[cfg(not(feature = "sqlite"))]
+fn is_missing_table(err: &dyn sqlx::error::DatabaseError) -> bool {
+    matches!(err.code().as_deref(), Some("42P01") | Some("42S02"))
+}
+
+#[cfg(feature = "sqlite")]
+fn is_missing_table(err: &dyn sqlx::error::DatabaseError) -> bool {
+    err.message().starts_with("no such table")
+}
+
 impl From<SqlxError> for Error {
     fn from(err: SqlxError) -> Self {
+        match &err {
+            SqlxError::PoolClosed => return Error::PoolClosed,
+            SqlxError::PoolTimedOut => return Error::Timeout,
+            SqlxError::Database(db_err) => {
+                if is_missing_table(&**db_err) {
+                    return Error::NotFound(err);
+                }
+                if db_err.code().as_deref() == Some("22001") {
+                    return Error::ValueTooLong(err);
+                }
+            }
+            _ => {}
+        }
         Error::SqlxError(err)
     }
 }
+
+impl From<Error> for CasbinError {
+    fn from(err: Error) -> Self {
+        err.into_casbin_error()
+    }
+}
+
+/// Turns the errors of sqlx, and of the adapter, into the casbin errors the
+/// adapter returns, for extensions that run their own queries next to it.
+///
+/// ```no_run
+/// # async fn example(adapter: sqlx_adapter::SqlxAdapter) -> casbin::Result<()> {
+/// use sqlx_adapter::ResultExt;
+///
+/// sqlx::query("SELECT 1").execute(adapter.pool()).await.adapt()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait ResultExt<T> {
+    fn adapt(self) -> casbin::Result<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn adapt(self) -> casbin::Result<T> {
+        self.map_err(|err| err.into().into_casbin_error())
+    }
+}
@@ -0,0 +1,13 @@
+/// The primary key of a table the adapter creates, see
+/// `SqlxAdapter::new_with_id_type`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IdType {
+    /// A 64-bit key from the table's sequence.
+    #[default]
+    BigInt,
+    /// A random version 4 UUID generated by the database, `UUID` on Postgres
+    /// (with `gen_random_uuid()`, built in from Postgres 13) and text
+    /// elsewhere (with `UUID()` on MySQL 8.0.13 or later). Loads in insertion
+    /// order then follow the id instead.
+    Uuid,
+}
@@ -0,0 +1,113 @@
+use crate::queries::{identifier, MAX_IDENTIFIER_LEN};
+
+/// A column of the rule table that can take part in an index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Column {
+    Ptype,
+    V0,
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+}
+
+impl Column {
+    pub(crate) const ALL: [Column; 7] = [
+        Column::Ptype,
+        Column::V0,
+        Column::V1,
+        Column::V2,
+        Column::V3,
+        Column::V4,
+        Column::V5,
+    ];
+    pub(crate) const VALUES: [Column; 6] = [
+        Column::V0,
+        Column::V1,
+        Column::V2,
+        Column::V3,
+        Column::V4,
+        Column::V5,
+    ];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Column::Ptype => "ptype",
+            Column::V0 => "v0",
+            Column::V1 => "v1",
+            Column::V2 => "v2",
+            Column::V3 => "v3",
+            Column::V4 => "v4",
+            Column::V5 => "v5",
+        }
+    }
+}
+
+/// An index over an ordered subset of the rule columns, for use with
+/// `SqlxAdapter::ensure_indexes` and `SqlxAdapter::drop_indexes`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IndexSpec {
+    columns: Vec<Column>,
+}
+
+impl IndexSpec {
+    pub fn new<I: IntoIterator<Item = Column>>(columns: I) -> Self {
+        Self {
+            columns: columns.into_iter().collect(),
+        }
+    }
+
+    /// `(ptype, v1)`, for removing every rule on an object with
+    /// `remove_filtered_policy` at field index 1.
+    pub fn object_scoped() -> Self {
+        Self::new(vec![Column::Ptype, Column::V1])
+    }
+
+    /// `(ptype, v3)`, for models that keep the domain in the fourth value.
+    pub fn domain_scoped() -> Self {
+        Self::new(vec![Column::Ptype, Column::V3])
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    // Names are built from the fixed column names only, so the index name and
+    // column list never carry user input besides the validated table name.
+    pub(crate) fn name(&self, table_name: &str) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| column.as_str())
+            .collect::<Vec<&str>>()
+            .join("_");
+        identifier(format!("idx_{}_{}", table_name, columns))
+    }
+
+    pub(crate) fn column_list(&self) -> String {
+        self.columns
+            .iter()
+            .map(|column| column.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+}
+
+/// An index on the rule table, as listed by `SqlxAdapter::list_indexes`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TableIndex {
+    pub name: String,
+    /// The indexed columns in index order, expressions as empty strings.
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+// Names go into SQL quoted but otherwise as given, so they are kept to what
+// every backend takes without quoting, and to a length none of them cuts.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_IDENTIFIER_LEN
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,21 @@
+/// The isolation level for the transactions behind `save_policy`,
+/// `add_policies` and `remove_policies`, see
+/// `SqlxAdapter::with_isolation_level`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IsolationLevel {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+// SQLite has no levels to choose from.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+impl IsolationLevel {
+    pub(crate) fn as_sql(self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
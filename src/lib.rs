@@ -1,14 +1,101 @@
 extern crate sqlx;
 
+#[cfg(not(any(feature = "postgres", feature = "mysql", feature = "sqlite")))]
+compile_error!(
+    "sqlx-adapter needs a database: enable exactly one of the features postgres, mysql and sqlite"
+);
+
+#[cfg(any(
+    all(feature = "postgres", feature = "mysql"),
+    all(feature = "postgres", feature = "sqlite"),
+    all(feature = "mysql", feature = "sqlite")
+))]
+compile_error!(
+    "the features postgres, mysql and sqlite of sqlx-adapter exclude each other: enable exactly one of them"
+);
+
 mod adapter;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod builder;
+mod connect_options;
+#[cfg(feature = "enforcer")]
+mod enforcer;
 mod error;
+mod id_type;
+mod index;
+mod isolation;
+mod load_order;
+mod metrics_hook;
+mod migrations;
+#[cfg(feature = "postgres")]
+mod notification;
+mod outbox_event;
+mod policy_csv;
+mod policy_event;
+#[cfg(feature = "serde")]
+mod policy_json;
+mod policy_rule;
+mod policy_store;
 
 #[macro_use]
 mod models;
 
+// Left out with more than one backend, so that the guard above is the
+// first error instead of the backend items defined several times.
+#[cfg(not(any(
+    all(feature = "postgres", feature = "mysql"),
+    all(feature = "postgres", feature = "sqlite"),
+    all(feature = "mysql", feature = "sqlite")
+)))]
 mod actions;
+#[cfg(all(feature = "compile-checked", feature = "postgres"))]
+mod checked;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(not(any(
+    all(feature = "postgres", feature = "mysql"),
+    all(feature = "postgres", feature = "sqlite"),
+    all(feature = "mysql", feature = "sqlite")
+)))]
+mod queries;
+mod report;
+mod rule_record;
+mod schema_report;
+mod section;
+mod slow_query;
+#[cfg(any(test, feature = "test-util"))]
+mod test_util;
+mod timestamped_rule;
 
 pub use casbin;
 
-pub use adapter::SqlxAdapter;
-pub use error::Error;
+pub use actions::ConnectionPool;
+pub use adapter::{DryRun, DryRunSave, FilteredRemoval, PoolStatus, SqlxAdapter};
+pub use builder::{SqlxAdapterBuilder, UninitializedAdapter};
+pub use connect_options::IntoConnectOptions;
+#[cfg(feature = "enforcer")]
+pub use enforcer::{new_enforcer, new_enforcer_with_pool};
+pub use error::{Error, ResultExt};
+pub use id_type::IdType;
+pub use index::{Column, IndexSpec, TableIndex};
+pub use isolation::IsolationLevel;
+pub use load_order::LoadOrder;
+#[cfg(feature = "metrics")]
+pub use metrics_hook::FacadeMetrics;
+pub use metrics_hook::{ErrorClass, MetricsHook, Operation, Outcome};
+pub use migrations::{migrations, Backend, MigrationStep};
+#[cfg(feature = "postgres")]
+pub use notification::apply_notification;
+pub use outbox_event::OutboxEvent;
+pub use policy_csv::{ImportMode, ImportSummary};
+pub use policy_event::{ChangedRules, PolicyEvent};
+pub use policy_rule::PolicyRule;
+pub use policy_store::PolicyStore;
+pub use report::{BatchReport, CopyReport, RuleOutcome};
+pub use rule_record::CasbinRuleRecord;
+pub use schema_report::{IncompatibleColumn, SchemaReport};
+pub use section::Section;
+#[cfg(feature = "test-util")]
+pub use test_util::{InMemoryPolicyStore, TestAdapter};
+pub use timestamped_rule::TimestampedRule;
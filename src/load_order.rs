@@ -0,0 +1,12 @@
+/// The order `load_policy` hands stored rules to the model in, see
+/// `SqlxAdapter::with_load_order`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LoadOrder {
+    /// Every rule in the order it was stored.
+    #[default]
+    Insertion,
+    /// Role links (`g` rules) first, then everything else, each in the order
+    /// it was stored. The role graph is complete before the first policy
+    /// rule arrives.
+    GroupingFirst,
+}
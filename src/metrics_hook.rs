@@ -0,0 +1,184 @@
+use crate::Error;
+use casbin::{error::AdapterError, Error as CasbinError};
+use std::time::Duration;
+
+/// Receives every policy operation once it is over, see
+/// `SqlxAdapter::with_metrics_hook`. It is called on the task that ran the
+/// operation, so it should only record and return.
+pub trait MetricsHook: Send + Sync {
+    fn on_operation(&self, op: Operation, duration: Duration, outcome: &Outcome);
+}
+
+/// A policy operation, as reported to a `MetricsHook`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Load,
+    LoadFiltered,
+    Save,
+    Add,
+    AddMany,
+    Remove,
+    RemoveMany,
+    RemoveFiltered,
+    Clear,
+    Exists,
+    Import,
+    SetEnabled,
+    SetDescription,
+}
+
+impl Operation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Operation::Load => "load",
+            Operation::LoadFiltered => "load_filtered",
+            Operation::Save => "save",
+            Operation::Add => "add",
+            Operation::AddMany => "add_many",
+            Operation::Remove => "remove",
+            Operation::RemoveMany => "remove_many",
+            Operation::RemoveFiltered => "remove_filtered",
+            Operation::Clear => "clear",
+            Operation::Exists => "exists",
+            Operation::Import => "import",
+            Operation::SetEnabled => "set_enabled",
+            Operation::SetDescription => "set_description",
+        }
+    }
+
+    // The adapter method reported in `Error::Context`.
+    pub(crate) fn method(self) -> &'static str {
+        match self {
+            Operation::Load => "load_policy",
+            Operation::LoadFiltered => "load_filtered_policy",
+            Operation::Save => "save_policy",
+            Operation::Add => "add_policy",
+            Operation::AddMany => "add_policies",
+            Operation::Remove => "remove_policy",
+            Operation::RemoveMany => "remove_policies",
+            Operation::RemoveFiltered => "remove_filtered_policy",
+            Operation::Clear => "clear_policy",
+            Operation::Exists => "has_policy",
+            Operation::Import => "import",
+            Operation::SetEnabled => "set_policy_enabled",
+            Operation::SetDescription => "set_policy_description",
+        }
+    }
+
+    // Adds the call and its table to the adapter's errors.
+    pub(crate) fn with_context(self, err: CasbinError, table: &str) -> CasbinError {
+        let err = match err {
+            CasbinError::AdapterError(AdapterError(err)) => err,
+            err => return err,
+        };
+        match err.downcast::<Error>() {
+            Ok(source) => {
+                let index = match *source {
+                    Error::AlreadyExists { index } => index,
+                    _ => None,
+                };
+                CasbinError::from(Error::Context {
+                    operation: self.method(),
+                    table: table.to_owned(),
+                    index,
+                    source,
+                })
+            }
+            Err(err) => CasbinError::AdapterError(AdapterError(err)),
+        }
+    }
+}
+
+/// How an operation ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// `rows` is the number of rules loaded, for the operations that load.
+    Success {
+        rows: Option<u64>,
+    },
+    Failure(ErrorClass),
+}
+
+/// What kind of error an operation failed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// No connection was free within the pool's acquire timeout.
+    PoolTimedOut,
+    /// See `Error::is_retryable`.
+    Retryable,
+    /// See `Error::CommitUnknown`.
+    CommitUnknown,
+    /// See `Error::AlreadyExists`.
+    AlreadyExists,
+    /// The rules or the call were refused before reaching the database.
+    Rejected,
+    /// Any other failure of the database or the connection to it.
+    Database,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorClass::PoolTimedOut => "pool_timed_out",
+            ErrorClass::Retryable => "retryable",
+            ErrorClass::CommitUnknown => "commit_unknown",
+            ErrorClass::AlreadyExists => "already_exists",
+            ErrorClass::Rejected => "rejected",
+            ErrorClass::Database => "database",
+            ErrorClass::Other => "other",
+        }
+    }
+
+    pub(crate) fn of(err: &CasbinError) -> Self {
+        let err = match err {
+            CasbinError::AdapterError(AdapterError(err)) => {
+                err.downcast_ref::<Error>().map(Error::inner)
+            }
+            _ => None,
+        };
+
+        match err {
+            Some(Error::Timeout) => ErrorClass::PoolTimedOut,
+            Some(err) if err.is_retryable() => ErrorClass::Retryable,
+            Some(Error::CommitUnknown(_)) => ErrorClass::CommitUnknown,
+            Some(Error::AlreadyExists { .. }) => ErrorClass::AlreadyExists,
+            Some(Error::SqlxError(_))
+            | Some(Error::NotFound(_))
+            | Some(Error::ValueTooLong(_))
+            | Some(Error::PoolClosed)
+            | Some(Error::CitextUnavailable(_)) => ErrorClass::Database,
+            Some(Error::Io(_)) => ErrorClass::Other,
+            Some(_) => ErrorClass::Rejected,
+            None => ErrorClass::Other,
+        }
+    }
+}
+
+/// Emits every operation through the `metrics` facade, as the counter
+/// `casbin_adapter_operations_total` and the histogram
+/// `casbin_adapter_operation_duration_seconds`, labelled with `operation`
+/// and `outcome`. Failures are labelled with their `ErrorClass`, and loads
+/// add their rules to `casbin_adapter_rows_loaded_total`.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FacadeMetrics;
+
+#[cfg(feature = "metrics")]
+impl MetricsHook for FacadeMetrics {
+    fn on_operation(&self, op: Operation, duration: Duration, outcome: &Outcome) {
+        let outcome_label = match outcome {
+            Outcome::Success { .. } => "success",
+            Outcome::Failure(class) => class.as_str(),
+        };
+        let labels = [("operation", op.as_str()), ("outcome", outcome_label)];
+
+        metrics::counter!("casbin_adapter_operations_total", &labels).increment(1);
+        metrics::histogram!("casbin_adapter_operation_duration_seconds", &labels)
+            .record(duration.as_secs_f64());
+        if let Outcome::Success { rows: Some(rows) } = outcome {
+            metrics::counter!("casbin_adapter_rows_loaded_total", "operation" => op.as_str())
+                .increment(*rows);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::queries::identifier;
+
+/// The database a `MigrationStep` is written for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Backend {
+    Postgres,
+    MySql,
+    Sqlite,
+}
+
+impl Backend {
+    // The backend the crate is built for.
+    #[cfg(feature = "postgres")]
+    pub(crate) const CURRENT: Backend = Backend::Postgres;
+    #[cfg(feature = "mysql")]
+    pub(crate) const CURRENT: Backend = Backend::MySql;
+    #[cfg(feature = "sqlite")]
+    pub(crate) const CURRENT: Backend = Backend::Sqlite;
+
+    pub(crate) fn quote(self, identifier: &str) -> String {
+        match self {
+            Backend::Postgres | Backend::Sqlite => {
+                format!("\"{}\"", identifier.replace('"', "\"\""))
+            }
+            Backend::MySql => format!("`{}`", identifier.replace('`', "``")),
+        }
+    }
+
+    pub(crate) const fn now(self) -> &'static str {
+        match self {
+            Backend::Postgres => "now()",
+            Backend::MySql => "CURRENT_TIMESTAMP(6)",
+            Backend::Sqlite => "strftime('%Y-%m-%d %H:%M:%f', 'now')",
+        }
+    }
+}
+
+/// A version of the rule table's schema, as returned by `migrations`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MigrationStep {
+    /// The version `run_migrations` records the step under.
+    pub version: i64,
+    pub description: &'static str,
+    /// The statements of the step, each ending with a semicolon. Empty for
+    /// steps that only upgrade tables made by older versions of the crate.
+    pub sql: String,
+}
+
+// The upgrades of the rule table, applied in order by `run_migrations`. Each
+// one checks what the table already has or is safe to repeat, so tables from
+// before the bookkeeping are taken through the whole chain and end up where
+// a fresh one does. Steps that do not apply to a backend are only recorded.
+pub(crate) const MIGRATIONS: &[(i64, &str)] = &[
+    (1, "create rule table"),
+    (2, "64-bit ids"),
+    (3, "identity ids"),
+    (4, "case-sensitive collation and 64-character ptype"),
+    (5, "created_at and updated_at"),
+];
+
+// Room for descriptive custom ptypes.
+pub(crate) const PTYPE_LENGTH: usize = 64;
+
+// The widest MySQL values that keep the unique key, ptype included, within
+// InnoDB's 3072-byte limit at utf8mb4's four bytes per character.
+pub(crate) const VALUE_LENGTH: usize = (3072 / 4 - PTYPE_LENGTH) / 6;
+
+/// The SQL that takes a new table named `table_name`, with 64-bit ids,
+/// through the schema versions of `run_migrations`, for migration pipelines
+/// that run the adapter's schema changes themselves. Applying every step
+/// and recording them in `_sqlx_adapter_migrations` leaves nothing for
+/// `run_migrations` to do. The Postgres steps need version 10 or later.
+///
+/// ```
+/// use sqlx_adapter::{migrations, Backend};
+///
+/// let steps = migrations(Backend::Postgres, "casbin_rule");
+/// assert_eq!(1, steps[0].version);
+/// assert!(steps[0].sql.starts_with("CREATE TABLE IF NOT EXISTS \"casbin_rule\""));
+/// ```
+pub fn migrations(backend: Backend, table_name: &str) -> Vec<MigrationStep> {
+    let table = backend.quote(table_name);
+    MIGRATIONS
+        .iter()
+        .map(|&(version, description)| {
+            let statements = match version {
+                1 => vec![create_table(
+                    backend,
+                    &table,
+                    table_name,
+                    bigint_id_column(backend),
+                )],
+                5 => timestamp_columns(backend, &table, table_name),
+                _ => Vec::new(),
+            };
+            MigrationStep {
+                version,
+                description,
+                sql: statements
+                    .iter()
+                    .map(|statement| format!("{};\n", statement))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn bigint_id_column(backend: Backend) -> &'static str {
+    match backend {
+        Backend::Postgres => "id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY",
+        Backend::MySql => "id BIGINT NOT NULL AUTO_INCREMENT",
+        Backend::Sqlite => "id INTEGER PRIMARY KEY",
+    }
+}
+
+// `table` is quoted, `table_name` is not.
+pub(crate) fn create_table(
+    backend: Backend,
+    table: &str,
+    table_name: &str,
+    id_column: &str,
+) -> String {
+    let unique_constraint = backend.quote(&identifier(format!(
+        "unique_key_sqlx_adapter_{}",
+        table_name
+    )));
+    match backend {
+        Backend::Postgres | Backend::Sqlite => format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                    {},
+                    ptype VARCHAR NOT NULL,
+                    v0 VARCHAR NOT NULL,
+                    v1 VARCHAR NOT NULL,
+                    v2 VARCHAR NOT NULL,
+                    v3 VARCHAR NOT NULL,
+                    v4 VARCHAR NOT NULL,
+                    v5 VARCHAR NOT NULL,
+                    CONSTRAINT {} UNIQUE(ptype, v0, v1, v2, v3, v4, v5)
+                    )",
+            table, id_column, unique_constraint
+        ),
+        Backend::MySql => format!(
+            "CREATE TABLE IF NOT EXISTS {0} (
+                    {1},
+                    ptype VARCHAR({2}) NOT NULL,
+                    v0 VARCHAR({3}) NOT NULL,
+                    v1 VARCHAR({3}) NOT NULL,
+                    v2 VARCHAR({3}) NOT NULL,
+                    v3 VARCHAR({3}) NOT NULL,
+                    v4 VARCHAR({3}) NOT NULL,
+                    v5 VARCHAR({3}) NOT NULL,
+                    PRIMARY KEY(id),
+                    CONSTRAINT {4} UNIQUE(ptype, v0, v1, v2, v3, v4, v5)
+                ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_bin",
+            table, id_column, PTYPE_LENGTH, VALUE_LENGTH, unique_constraint
+        ),
+    }
+}
+
+// SQLite only adds columns with constant defaults, so existing rows are
+// backfilled by hand and a trigger stamps the rows inserted later.
+pub(crate) fn timestamp_columns(backend: Backend, table: &str, table_name: &str) -> Vec<String> {
+    match backend {
+        Backend::Postgres => vec![format!(
+            "ALTER TABLE {}
+            ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now()",
+            table
+        )],
+        Backend::MySql => vec![format!(
+            "ALTER TABLE {}
+            ADD COLUMN created_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+            ADD COLUMN updated_at DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6)
+                ON UPDATE CURRENT_TIMESTAMP(6)",
+            table
+        )],
+        Backend::Sqlite => vec![
+            format!("ALTER TABLE {} ADD COLUMN created_at TEXT", table),
+            format!("ALTER TABLE {} ADD COLUMN updated_at TEXT", table),
+            format!(
+                "UPDATE {} SET created_at = {1}, updated_at = {1}",
+                table,
+                backend.now()
+            ),
+            format!(
+                "CREATE TRIGGER {} AFTER INSERT ON {1} FOR EACH ROW WHEN NEW.created_at IS NULL
+                BEGIN UPDATE {1} SET created_at = {2}, updated_at = {2} WHERE id = NEW.id; END",
+                backend.quote(&identifier(format!(
+                    "sqlx_adapter_timestamps_{}",
+                    table_name
+                ))),
+                table,
+                backend.now()
+            ),
+        ],
+    }
+}
@@ -1,24 +1,48 @@
-use sqlx::FromRow;
+use crate::actions::Db;
+use sqlx::{error::BoxDynError, Database, Decode, FromRow, Type, ValueRef};
+use std::fmt;
 
-#[allow(dead_code)]
-#[cfg(any(feature = "postgres", feature = "mysql"))]
-#[derive(Debug, FromRow)]
-pub(crate) struct CasbinRule {
-    pub id: i32,
-    pub ptype: String,
-    pub v0: String,
-    pub v1: String,
-    pub v2: String,
-    pub v3: String,
-    pub v4: String,
-    pub v5: String,
+// Sequence ids are read as integers and UUIDs as text, whichever the table
+// holds.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum RuleId {
+    BigInt(i64),
+    Uuid(String),
+}
+
+impl fmt::Display for RuleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleId::BigInt(id) => id.fmt(f),
+            RuleId::Uuid(id) => id.fmt(f),
+        }
+    }
+}
+
+impl Type<Db> for RuleId {
+    fn type_info() -> <Db as Database>::TypeInfo {
+        <i64 as Type<Db>>::type_info()
+    }
+
+    fn compatible(ty: &<Db as Database>::TypeInfo) -> bool {
+        <i64 as Type<Db>>::compatible(ty) || <String as Type<Db>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Db> for RuleId {
+    fn decode(value: <Db as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        if <String as Type<Db>>::compatible(&value.type_info()) {
+            <String as Decode<Db>>::decode(value).map(RuleId::Uuid)
+        } else {
+            <i64 as Decode<Db>>::decode(value).map(RuleId::BigInt)
+        }
+    }
 }
 
 #[allow(dead_code)]
-#[cfg(feature = "sqlite")]
 #[derive(Debug, FromRow)]
 pub(crate) struct CasbinRule {
-    pub id: i64,
+    pub id: RuleId,
     pub ptype: String,
     pub v0: String,
     pub v1: String,
@@ -26,9 +50,21 @@ pub(crate) struct CasbinRule {
     pub v3: String,
     pub v4: String,
     pub v5: String,
+    // Microseconds since the Unix epoch, only selected by
+    // `rules_with_timestamps`.
+    #[sqlx(default)]
+    pub created_at: Option<i64>,
+    #[sqlx(default)]
+    pub updated_at: Option<i64>,
+    // Only selected with `with_modified_by`.
+    #[sqlx(default)]
+    pub modified_by: Option<String>,
+    // Only selected with `with_descriptions`.
+    #[sqlx(default)]
+    pub description: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub(crate) struct NewCasbinRule<'a> {
     pub ptype: &'a str,
     pub v0: &'a str,
@@ -38,3 +74,9 @@ pub(crate) struct NewCasbinRule<'a> {
     pub v4: &'a str,
     pub v5: &'a str,
 }
+
+impl<'a> NewCasbinRule<'a> {
+    pub(crate) fn values(&self) -> [&'a str; 6] {
+        [self.v0, self.v1, self.v2, self.v3, self.v4, self.v5]
+    }
+}
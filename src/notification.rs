@@ -0,0 +1,72 @@
+use casbin::Model;
+use serde_json::Value;
+
+/// Applies a notification sent by an adapter set up with
+/// `SqlxAdapter::with_notify` to `m`, one rule or filter at a time, and
+/// returns whether it could. Saves, clears, imports and batches too large
+/// for a notification arrive as bulk markers, for which it returns false and
+/// leaves `m` alone, so that the caller reloads the whole policy. Changes to
+/// g rules also need the role links rebuilt, with `CoreApi::build_role_links`.
+///
+/// Notifications carry the tenant of the adapter that sent them, it is up
+/// to the caller to skip those of other tenants.
+pub fn apply_notification(m: &mut dyn Model, payload: &str) -> bool {
+    let event: Value = match serde_json::from_str(payload) {
+        Ok(event) => event,
+        Err(_) => return false,
+    };
+    let (op, ptype) = match (event["op"].as_str(), event["ptype"].as_str()) {
+        (Some(op), Some(ptype)) if !ptype.is_empty() => (op, ptype),
+        _ => return false,
+    };
+    // Models keep each ptype under the section of its first letter.
+    let sec = &ptype[..ptype.chars().next().map_or(0, char::len_utf8)];
+
+    let values = &event["values"];
+    match op {
+        "add" => match strings(values) {
+            Some(rule) => {
+                m.add_policy(sec, ptype, rule);
+            }
+            None => return false,
+        },
+        "add_many" => match rules(values) {
+            Some(rules) => {
+                m.add_policies(sec, ptype, rules);
+            }
+            None => return false,
+        },
+        "remove" => match strings(values) {
+            Some(rule) => {
+                m.remove_policy(sec, ptype, rule);
+            }
+            None => return false,
+        },
+        "remove_many" => match rules(values) {
+            Some(rules) => {
+                m.remove_policies(sec, ptype, rules);
+            }
+            None => return false,
+        },
+        "remove_filtered" => match (event["field_index"].as_u64(), strings(values)) {
+            (Some(field_index), Some(field_values)) => {
+                m.remove_filtered_policy(sec, ptype, field_index as usize, field_values);
+            }
+            _ => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+fn strings(value: &Value) -> Option<Vec<String>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|value| value.as_str().map(str::to_owned))
+        .collect()
+}
+
+fn rules(value: &Value) -> Option<Vec<Vec<String>>> {
+    value.as_array()?.iter().map(strings).collect()
+}
@@ -0,0 +1,121 @@
+use crate::{
+    load_order::LoadOrder,
+    metrics_hook::{ErrorClass, Operation},
+    queries::Queries,
+    Error,
+};
+use casbin::{error::AdapterError, Error as CasbinError, Result};
+use std::future::Future;
+use tracing::{field, Instrument, Level, Span};
+
+#[cfg(feature = "postgres")]
+pub(crate) const DB_SYSTEM: &str = "postgresql";
+#[cfg(feature = "mysql")]
+pub(crate) const DB_SYSTEM: &str = "mysql";
+#[cfg(feature = "sqlite")]
+pub(crate) const DB_SYSTEM: &str = "sqlite";
+
+// Longer statements are cut short in `db.statement`.
+const MAX_STATEMENT_LEN: usize = 1024;
+
+// Runs a policy operation inside a client span carrying the OpenTelemetry
+// database attributes, which tracing-opentelemetry exports as they are.
+pub(crate) async fn traced<T>(
+    queries: &Queries,
+    op: Operation,
+    order: LoadOrder,
+    call: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let sql_operation = sql_operation(op);
+    let span = tracing::info_span!(
+        "casbin_adapter",
+        otel.name = %format_args!("{} {}", sql_operation, queries.table_name),
+        otel.kind = "client",
+        otel.status_code = field::Empty,
+        otel.status_message = field::Empty,
+        db.system = DB_SYSTEM,
+        db.sql.table = &*queries.table_name,
+        db.operation = sql_operation,
+        db.statement = field::Empty,
+        casbin.operation = op.as_str(),
+        "error.type" = field::Empty,
+    );
+    if let Some(statement) = statement(queries, op, order) {
+        span.record("db.statement", sanitize(statement).as_str());
+    }
+
+    let result = call.instrument(span.clone()).await;
+    if let Err(err) = &result {
+        record_error(&span, err);
+    }
+    result
+}
+
+fn sql_operation(op: Operation) -> &'static str {
+    match op {
+        Operation::Load | Operation::LoadFiltered | Operation::Exists => "SELECT",
+        Operation::Save | Operation::Add | Operation::AddMany | Operation::Import => "INSERT",
+        Operation::Remove
+        | Operation::RemoveMany
+        | Operation::RemoveFiltered
+        | Operation::Clear => "DELETE",
+        Operation::SetEnabled | Operation::SetDescription => "UPDATE",
+    }
+}
+
+// The prepared statement an operation runs, None for those that run several
+// or build theirs per call. Values are always bound, so the text holds none.
+fn statement(queries: &Queries, op: Operation, order: LoadOrder) -> Option<&str> {
+    match op {
+        Operation::Load => Some(queries.load(order)),
+        Operation::Add => Some(&queries.insert),
+        Operation::Remove => Some(&queries.remove),
+        Operation::Exists => Some(&queries.exists),
+        Operation::Clear => Some(&queries.clear),
+        Operation::SetDescription => Some(&queries.set_description),
+        Operation::LoadFiltered
+        | Operation::Save
+        | Operation::AddMany
+        | Operation::RemoveMany
+        | Operation::RemoveFiltered
+        | Operation::Import
+        | Operation::SetEnabled => None,
+    }
+}
+
+fn sanitize(statement: &str) -> String {
+    let mut sanitized = statement
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if sanitized.len() > MAX_STATEMENT_LEN {
+        let mut end = MAX_STATEMENT_LEN;
+        while !sanitized.is_char_boundary(end) {
+            end -= 1;
+        }
+        sanitized.truncate(end);
+        sanitized.push_str("...");
+    }
+    sanitized
+}
+
+// Marks the span failed and adds the `exception` event of the semantic
+// conventions.
+fn record_error(span: &Span, err: &CasbinError) {
+    let exception_type = match err {
+        CasbinError::AdapterError(AdapterError(err)) if err.is::<Error>() => "sqlx_adapter::Error",
+        _ => "casbin::Error",
+    };
+    let message = err.to_string();
+
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_message", message.as_str());
+    span.record("error.type", ErrorClass::of(err).as_str());
+    tracing::event!(
+        parent: span,
+        Level::ERROR,
+        "exception.type" = exception_type,
+        exception.message = message.as_str(),
+        message = "exception",
+    );
+}
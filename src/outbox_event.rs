@@ -0,0 +1,20 @@
+use serde_json::Value;
+use std::time::SystemTime;
+
+/// A change to the policy recorded in the outbox, as returned by
+/// `SqlxAdapter::poll_outbox`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboxEvent {
+    /// Increasing in the order the changes were committed in, within one
+    /// outbox.
+    pub id: i64,
+    /// The `Operation` that made the change, as its `as_str` name.
+    pub op: String,
+    /// The `ptype` of the change, or null for changes that may span ptypes,
+    /// along with either the changed `rules`, each a `ptype` and `values`,
+    /// the `field_index` and `field_values` of a filtered removal, or
+    /// `"all": true` for changes to every rule. Adapters for a tenant add
+    /// its `tenant`.
+    pub payload: Value,
+    pub created_at: SystemTime,
+}
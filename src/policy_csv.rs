@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+/// What `SqlxAdapter::import_csv` does about the rules already stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImportMode {
+    /// Clears the table first, like `save_policy` with the file's rules.
+    Replace,
+    /// Keeps the stored rules and skips the file's rules among them.
+    Merge,
+    /// Keeps the stored rules and fails the import with
+    /// `Error::AlreadyExists` if the file repeats one of them, or itself.
+    Strict,
+}
+
+/// The counts of `SqlxAdapter::import_csv`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    imported: BTreeMap<String, u64>,
+    skipped: u64,
+}
+
+impl ImportSummary {
+    pub(crate) fn expect(&mut self, ptype: &str) {
+        self.imported.entry(ptype.to_owned()).or_insert(0);
+    }
+
+    pub(crate) fn add(&mut self, ptype: &str, imported: u64) {
+        *self.imported.entry(ptype.to_owned()).or_insert(0) += imported;
+    }
+
+    pub(crate) fn skip(&mut self, skipped: u64) {
+        self.skipped += skipped;
+    }
+
+    /// The rules stored, by ptype, for every ptype of the file.
+    pub fn imported(&self) -> &BTreeMap<String, u64> {
+        &self.imported
+    }
+
+    pub fn total(&self) -> u64 {
+        self.imported.values().sum()
+    }
+
+    /// The rules left out because the file repeats them or, with
+    /// `ImportMode::Merge`, because they were already stored.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+// The line `parse_policy_csv` reads back as the rule, without its line
+// break. Fields are quoted when they hold a comma or a quote, or whitespace
+// the reader would trim.
+pub(crate) fn format_policy_line(ptype: &str, values: &[String]) -> String {
+    let mut line = String::new();
+    for field in std::iter::once(ptype).chain(values.iter().map(String::as_str)) {
+        if !line.is_empty() {
+            line.push_str(", ");
+        }
+        let quoted = field.contains([',', '"'])
+            || field.trim().len() != field.len()
+            || (line.is_empty() && field.starts_with('#'));
+        if quoted {
+            line.push('"');
+            line.push_str(&field.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(field);
+        }
+    }
+    line
+}
+
+// A policy line of the file and its number, counted from 1.
+pub(crate) struct CsvRecord {
+    pub line: usize,
+    pub ptype: String,
+    pub values: Vec<String>,
+}
+
+// Casbin's policy format: a ptype and its values separated by commas, with
+// the whitespace around each field dropped. Fields in double quotes keep
+// their commas and whitespace, and `""` stands for a quote inside them.
+// Blank lines and those starting with `#` are skipped.
+pub(crate) fn parse_policy_csv(text: &str) -> Result<Vec<CsvRecord>, (usize, &'static str)> {
+    let mut records = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = parse_line(line).map_err(|reason| (line_number, reason))?;
+        let ptype = fields.remove(0);
+        if ptype.is_empty() {
+            return Err((line_number, "line has no ptype"));
+        }
+        if fields.is_empty() {
+            return Err((line_number, "line has no values"));
+        }
+        records.push(CsvRecord {
+            line: line_number,
+            ptype,
+            values: fields,
+        });
+    }
+    Ok(records)
+}
+
+fn parse_line(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut field = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err("quoted field is not closed"),
+                }
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if !matches!(chars.peek(), None | Some(',')) {
+                return Err("quoted field is followed by more than whitespace");
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                if c == '"' {
+                    return Err("quote inside an unquoted field");
+                }
+                field.push(c);
+            }
+            field.truncate(field.trim_end().len());
+        }
+        fields.push(field);
+
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
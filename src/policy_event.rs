@@ -0,0 +1,28 @@
+use crate::metrics_hook::Operation;
+
+/// A committed change to the stored rules, as handed to the callback of
+/// `SqlxAdapter::set_on_change`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyEvent {
+    /// The operation that made the change. Calls outside casbin's `Adapter`
+    /// trait report the closest one, `Add` for `add_policy_with_metadata`
+    /// or `Save` for `save_section_policy` for instance.
+    pub operation: Operation,
+    /// None for changes that may span ptypes, like saves and clears.
+    pub ptype: Option<String>,
+    pub rules: ChangedRules,
+}
+
+/// The rules a `PolicyEvent` is about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangedRules {
+    /// These rules, normalized as they were stored or removed.
+    Rules(Vec<Vec<String>>),
+    /// The rules matching the values of a `remove_filtered_policy` call.
+    Filtered {
+        field_index: usize,
+        field_values: Vec<String>,
+    },
+    /// Any rule, of the event's ptype if it has one.
+    All,
+}
@@ -0,0 +1,60 @@
+use serde_json::Value;
+
+// An entry of the document that is not a rule, named like `p[3]` or
+// `p[3][1]`, or None for the document itself, and why.
+pub(crate) type JsonError = (Option<String>, String);
+
+// A rule of the document and the entry it came from.
+pub(crate) struct JsonRecord {
+    pub entry: String,
+    pub ptype: String,
+    pub values: Vec<String>,
+}
+
+// The rules of `{"p": [["alice", "data1", "read"], ...], "g": [...]}`,
+// ptypes in name order and their rules in the order given. Any ptype is
+// accepted.
+pub(crate) fn parse_policy_json(text: &str) -> Result<Vec<JsonRecord>, JsonError> {
+    let document = serde_json::from_str::<Value>(text).map_err(|err| (None, err.to_string()))?;
+    let ptypes = match document {
+        Value::Object(ptypes) => ptypes,
+        _ => return Err((None, "expected an object of ptypes".to_owned())),
+    };
+
+    let mut records = Vec::new();
+    for (ptype, rules) in ptypes {
+        let rules = match rules {
+            Value::Array(rules) => rules,
+            _ => return Err((Some(ptype), "expected an array of rules".to_owned())),
+        };
+        if ptype.trim().is_empty() {
+            return Err((Some(ptype), "ptype is empty".to_owned()));
+        }
+
+        for (index, rule) in rules.into_iter().enumerate() {
+            let entry = format!("{}[{}]", ptype, index);
+            let values = match rule {
+                Value::Array(values) if !values.is_empty() => values,
+                Value::Array(_) => return Err((Some(entry), "rule has no values".to_owned())),
+                _ => return Err((Some(entry), "expected an array of values".to_owned())),
+            };
+            let values = values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| match value {
+                    Value::String(value) => Ok(value),
+                    _ => Err((
+                        Some(format!("{}[{}]", entry, i)),
+                        "value is not a string".to_owned(),
+                    )),
+                })
+                .collect::<Result<Vec<String>, JsonError>>()?;
+            records.push(JsonRecord {
+                entry,
+                ptype: ptype.clone(),
+                values,
+            });
+        }
+    }
+    Ok(records)
+}
@@ -0,0 +1,210 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{
+    adapter::MAX_RULE_FIELDS, error::Error, policy_csv::format_policy_line,
+    rule_record::trim_trailing_empty, CasbinRuleRecord, TimestampedRule,
+};
+
+/// A rule as its ptype and values, for telling the two apart where casbin
+/// passes them separately. Trailing empty values are dropped, so a rule
+/// compares equal however much padding it came with. Deserializing checks
+/// it like `PolicyRule::new`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerializedRule")
+)]
+pub struct PolicyRule {
+    ptype: String,
+    values: Vec<String>,
+}
+
+impl PolicyRule {
+    /// Fails with `Error::EmptyRule` for an empty ptype or without a
+    /// non-empty value, and with `Error::TooManyFields` for more than the
+    /// six values a table holds.
+    pub fn new<P: Into<String>>(ptype: P, values: Vec<String>) -> Result<Self, Error> {
+        let ptype = ptype.into();
+        let values = trim_trailing_empty(values);
+        if values.len() > MAX_RULE_FIELDS {
+            return Err(Error::TooManyFields {
+                expected: MAX_RULE_FIELDS,
+                got: values.len(),
+            });
+        }
+        if ptype.is_empty() || values.is_empty() {
+            return Err(Error::EmptyRule);
+        }
+        Ok(Self { ptype, values })
+    }
+
+    pub fn ptype(&self) -> &str {
+        &self.ptype
+    }
+
+    /// The values in the `Vec<String>` form casbin uses.
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+
+    pub fn into_parts(self) -> (String, Vec<String>) {
+        (self.ptype, self.values)
+    }
+
+    // Stored rules went through the same checks.
+    pub(crate) fn stored(ptype: String, values: Vec<String>) -> Self {
+        Self {
+            ptype,
+            values: trim_trailing_empty(values),
+        }
+    }
+}
+
+impl TryFrom<(String, Vec<String>)> for PolicyRule {
+    type Error = Error;
+
+    fn try_from((ptype, values): (String, Vec<String>)) -> Result<Self, Error> {
+        Self::new(ptype, values)
+    }
+}
+
+// The fields of a serialized rule, before they are checked.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerializedRule {
+    ptype: String,
+    values: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerializedRule> for PolicyRule {
+    type Error = Error;
+
+    fn try_from(rule: SerializedRule) -> Result<Self, Error> {
+        Self::new(rule.ptype, rule.values)
+    }
+}
+
+impl From<PolicyRule> for (String, Vec<String>) {
+    fn from(rule: PolicyRule) -> Self {
+        rule.into_parts()
+    }
+}
+
+impl From<CasbinRuleRecord> for PolicyRule {
+    fn from(record: CasbinRuleRecord) -> Self {
+        record.rule
+    }
+}
+
+impl From<TimestampedRule> for PolicyRule {
+    fn from(rule: TimestampedRule) -> Self {
+        rule.rule
+    }
+}
+
+/// The rule as a line of a policy file, `p, alice, data1, read`.
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_policy_line(&self.ptype, &self.values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_conversions() {
+        let rule = PolicyRule::new("p", values(&["alice", "data1", "read"])).unwrap();
+        assert_eq!("p", rule.ptype());
+        assert_eq!(values(&["alice", "data1", "read"]), rule.values());
+        assert_eq!("p, alice, data1, read", rule.to_string());
+        assert_eq!(
+            rule,
+            PolicyRule::try_from(("p".to_owned(), values(&["alice", "data1", "read"]))).unwrap()
+        );
+        assert_eq!(
+            ("p".to_owned(), values(&["alice", "data1", "read"])),
+            rule.into()
+        );
+
+        let record = CasbinRuleRecord::new(
+            "1",
+            PolicyRule::new("p", values(&["bob", "data2"])).unwrap(),
+        );
+        assert_eq!(
+            PolicyRule::new("p", values(&["bob", "data2"])).unwrap(),
+            PolicyRule::from(record)
+        );
+    }
+
+    #[test]
+    fn test_trailing_empty_values() {
+        let rule = PolicyRule::new("p", values(&["alice", "data1", "read", "", ""])).unwrap();
+        assert_eq!(values(&["alice", "data1", "read"]), rule.values());
+        assert_eq!(
+            PolicyRule::new("p", values(&["alice", "data1", "read"])).unwrap(),
+            rule
+        );
+        assert_eq!(
+            rule,
+            PolicyRule::stored(
+                "p".to_owned(),
+                values(&["alice", "data1", "read", "", "", ""])
+            )
+        );
+
+        // Inner empty values are kept, and values are quoted as in policy
+        // files.
+        let rule = PolicyRule::new("g", values(&["alice", "", "a, b"])).unwrap();
+        assert_eq!(3, rule.values().len());
+        assert_eq!("g, alice, , \"a, b\"", rule.to_string());
+
+        assert!(matches!(
+            PolicyRule::new("p", values(&["", ""])),
+            Err(Error::EmptyRule)
+        ));
+        assert!(matches!(
+            PolicyRule::new("", values(&["alice"])),
+            Err(Error::EmptyRule)
+        ));
+    }
+
+    #[test]
+    fn test_arity_limits() {
+        assert!(PolicyRule::new("p", values(&["a", "b", "c", "d", "e", "f"])).is_ok());
+        // Trailing empty values do not count.
+        assert!(PolicyRule::new("p", values(&["a", "b", "c", "d", "e", "f", ""])).is_ok());
+        assert!(matches!(
+            PolicyRule::new("p", values(&["a", "b", "c", "d", "e", "f", "g"])),
+            Err(Error::TooManyFields {
+                expected: 6,
+                got: 7
+            })
+        ));
+        assert!(matches!(
+            PolicyRule::try_from(("p".to_owned(), values(&["a", "b", "c", "d", "e", "", "g"]))),
+            Err(Error::TooManyFields { .. })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let rule = PolicyRule::new("g", values(&["alice", "", "a, b"])).unwrap();
+        let json = serde_json::to_string(&rule).unwrap();
+        assert_eq!(rule, serde_json::from_str::<PolicyRule>(&json).unwrap());
+        assert!(serde_json::from_str::<PolicyRule>(r#"{"ptype":"p","values":[""]}"#).is_err());
+        assert!(serde_json::from_str::<PolicyRule>(
+            r#"{"ptype":"p","values":["a","b","c","d","e","f","g"]}"#
+        )
+        .is_err());
+    }
+}
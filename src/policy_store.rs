@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use casbin::{Filter, Model, Result};
+
+use crate::policy_rule::PolicyRule;
+
+/// The storage behind the policy operations of casbin's `Adapter` trait.
+/// `SqlxAdapter` is one on its own tables, and runs them against another
+/// one given to `SqlxAdapter::new_with_store`, such as the
+/// `InMemoryPolicyStore` of the `test-util` feature.
+///
+/// The adapter checks and normalizes the rules of its calls before they get
+/// here, and keeps track of filtered loads, so stores only keep the rules.
+/// Duplicates are refused with `Error::AlreadyExists`, carrying the index of
+/// the offending rule for `add_many` and `save`.
+#[async_trait]
+pub trait PolicyStore: Send + Sync {
+    /// Adds every stored rule to `m` and returns how many there were.
+    async fn load(&self, m: &mut dyn Model) -> Result<u64>;
+
+    /// Adds the stored rules that `f` lets through to `m` and returns how
+    /// many there were. The values of `f.p` and `f.g` are compared from v0
+    /// on, an empty one matching any, and rules of other sections are all
+    /// loaded.
+    async fn load_filtered(&self, m: &mut dyn Model, f: &Filter<'_>) -> Result<u64>;
+
+    async fn add(&self, ptype: &str, rule: Vec<String>) -> Result<bool>;
+
+    /// Stores all of `rules` or none of them.
+    async fn add_many(&self, ptype: &str, rules: Vec<Vec<String>>) -> Result<bool>;
+
+    async fn remove(&self, ptype: &str, rule: Vec<String>) -> Result<bool>;
+
+    /// Removes all of `rules`, or none of them and returns false when one is
+    /// not stored.
+    async fn remove_many(&self, ptype: &str, rules: Vec<Vec<String>>) -> Result<bool>;
+
+    /// Removes the rules of `ptype` whose values from `field_index` on
+    /// match `field_values`, where an empty value matches any. A
+    /// `field_index` past v5 matches none.
+    async fn remove_filtered(
+        &self,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool>;
+
+    /// Stores a batch of `SqlxAdapter::copy_to`, whose rules may be of
+    /// several ptypes, and returns how many it stored. Rules already stored
+    /// are skipped, or with `strict` fail the batch with
+    /// `Error::AlreadyExists`.
+    async fn import(&self, rules: Vec<PolicyRule>, strict: bool) -> Result<u64>;
+
+    /// Replaces every stored rule with the policies of `m`.
+    async fn save(&self, m: &dyn Model) -> Result<()>;
+
+    async fn clear(&self) -> Result<()>;
+}
@@ -0,0 +1,901 @@
+use crate::{
+    actions::Db, id_type::IdType, index::Column, load_order::LoadOrder, migrations::Backend,
+    section::Section, Error,
+};
+use sqlx::QueryBuilder;
+use std::sync::Arc;
+
+// How the table is read and written, beyond its name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct QueryOptions {
+    // An empty string is the unset value, and adapter-created columns are NOT
+    // NULL. Tables from elsewhere may hold NULLs instead, `legacy_nulls` reads
+    // them as empty strings at the cost of the unique index.
+    pub legacy_nulls: bool,
+    // Removed rules get a `deleted_at` time instead of being deleted, and
+    // everything else only sees the rules without one.
+    pub soft_delete: bool,
+    // Rules are stored with this tenant and every statement only sees the
+    // rules stored with it.
+    pub tenant: Option<Arc<str>>,
+    // Every change to the policy bumps the revision in the same transaction.
+    pub revisions: bool,
+    // Every change to the policy also records an event in this table, the
+    // outbox of the adapter's main table, in the same transaction.
+    pub outbox: Option<Arc<str>>,
+    // Every change to the policy also sends a NOTIFY on this channel.
+    #[cfg(feature = "postgres")]
+    pub notify: Option<Arc<str>>,
+    pub id_type: IdType,
+    // Loads skip the rules with `enabled` off, and save_policy leaves them
+    // in place.
+    pub enabled_flag: bool,
+    // Loads and lookups skip the rules whose `expires_at` has passed, by the
+    // database's clock.
+    pub expiry: bool,
+    // Rules are read with their `modified_by`, and written with the actor in
+    // it, or NULL without one.
+    pub modified_by: bool,
+    pub actor: Option<Arc<str>>,
+    // Rules are read with their `description`, and save_policy carries the
+    // descriptions of the rules it stores again over.
+    pub descriptions: bool,
+    // Updates to the rules set `updated_at` to the database's current time.
+    pub timestamps: bool,
+    // Inserts fill in `rule_hash`, and exact-rule lookups compare it along
+    // with the rule so that the planner can seek on its index.
+    pub rule_hash: bool,
+    // Columns of type CITEXT, whose values are compared case-insensitively.
+    #[cfg(feature = "postgres")]
+    pub case_insensitive: Vec<Column>,
+}
+
+impl QueryOptions {
+    #[cfg(feature = "postgres")]
+    pub(crate) fn is_case_insensitive(&self, column: Column) -> bool {
+        self.case_insensitive.contains(&column)
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "mysql"))]
+    pub(crate) fn is_case_insensitive(&self, _column: Column) -> bool {
+        false
+    }
+
+    // Postgres compares a CITEXT column with a text value as text, case and
+    // all, so values compared with one are cast to it.
+    pub(crate) fn compared(&self, column: Column, value: String) -> String {
+        if self.is_case_insensitive(column) {
+            format!("CAST({} AS CITEXT)", value)
+        } else {
+            value
+        }
+    }
+
+    // The hash holds the values as written, so it only narrows exact-rule
+    // lookups while every column compares them that way too.
+    pub(crate) fn hash_lookups(&self) -> bool {
+        self.rule_hash
+            && !Column::ALL
+                .iter()
+                .any(|&column| self.is_case_insensitive(column))
+    }
+}
+
+// SQL text is generated once per adapter so that every call sends a
+// byte-identical statement and hits sqlx's per-connection statement cache.
+#[derive(Clone, Debug)]
+pub(crate) struct Queries {
+    pub table_name: Arc<str>,
+    pub options: QueryOptions,
+    // The table name quoted for use in SQL, the raw one only names things.
+    pub table: Arc<str>,
+    // The expression each value column is compared and read through.
+    pub columns: [Arc<str>; 6],
+    #[cfg(feature = "postgres")]
+    pub rule_columns: Arc<str>,
+    pub select: Arc<str>,
+    pub unique_constraint: Arc<str>,
+    pub insert: Arc<str>,
+    pub insert_with_metadata: Arc<str>,
+    pub insert_with_enabled: Arc<str>,
+    pub insert_with_expiry: Arc<str>,
+    pub metadata: Arc<str>,
+    pub enable_rule: Arc<str>,
+    pub disable_rule: Arc<str>,
+    pub list_disabled: Arc<str>,
+    pub set_description: Arc<str>,
+    pub described: Arc<str>,
+    pub remove: Arc<str>,
+    pub exists: Arc<str>,
+    pub remove_filtered: [RemoveFiltered; 6],
+    // The start of a statement removing the rules matched by the conditions
+    // that follow it.
+    pub removal: Arc<str>,
+    // Held by save_policy for its whole transaction, so that concurrent
+    // saves to the same table run one after the other.
+    #[cfg(feature = "postgres")]
+    pub save_lock: i64,
+    #[cfg(feature = "mysql")]
+    pub save_lock: Arc<str>,
+    pub remove_ptype: Arc<str>,
+    pub clear: Arc<str>,
+    // What save_policy clears before inserting the model's rules.
+    pub save_clear: Arc<str>,
+    // save_clear for the p and the g section of a table holding both.
+    section_clear: [Arc<str>; 2],
+    // None in soft-delete mode, where rules are only ever marked deleted,
+    // for tenants and with the enabled flag.
+    #[cfg(feature = "postgres")]
+    pub truncate: Option<Arc<str>>,
+    load_by_id: Arc<str>,
+    pub export: Arc<str>,
+    load_grouping_first: Arc<str>,
+    // Filters only apply to the g and p sections, rules of any other ptype
+    // are loaded in full.
+    pub load_unsectioned: Arc<str>,
+    pub load_timestamped: Arc<str>,
+    pub list_page: Arc<str>,
+    // Every rule, or those of one ptype, in id order.
+    pub stream: Arc<str>,
+    pub stream_ptype: Arc<str>,
+    pub purge_deleted: Arc<str>,
+    pub purge_expired: Arc<str>,
+    // Added to the scope of loads, followed by a condition.
+    load_conditions: Arc<str>,
+    pub revision_table: Arc<str>,
+    pub seed_revision: Arc<str>,
+    pub current_revision: Arc<str>,
+    // None unless the adapter keeps revisions.
+    pub bump_revision: Option<Arc<str>>,
+    // The outbox of `options.outbox`, or the table's own without one.
+    pub outbox_table: Arc<str>,
+    pub poll_outbox: Arc<str>,
+    // None unless the adapter keeps an outbox.
+    pub insert_outbox: Option<Arc<str>>,
+    // Fill in `rule_hash` for rows written without it, across tenants.
+    pub unhashed: Arc<str>,
+    pub backfill_hash: Arc<str>,
+    pub applied_migrations: Arc<str>,
+    pub record_migration: Arc<str>,
+    #[cfg(any(test, feature = "test-util"))]
+    pub forget_migrations: Arc<str>,
+}
+
+// Rows hashed per transaction by `add_rule_hash_column`.
+const HASH_BACKFILL_BATCH: usize = 500;
+
+// Which migrations each rule table has had, keyed by its unquoted name.
+pub(crate) const MIGRATIONS_TABLE: &str = "_sqlx_adapter_migrations";
+
+// MySQL refuses identifiers over 64 characters and Postgres cuts them at 63
+// bytes, so longer generated names keep a prefix and end in a hash of the
+// whole name. Names that fit are left as they are.
+pub(crate) const MAX_IDENTIFIER_LEN: usize = 63;
+
+// FNV-1a, which unlike std's hashers is stable across releases.
+fn fnv1a<I: IntoIterator<Item = u8>>(bytes: I) -> u64 {
+    bytes
+        .into_iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+// The value of the `rule_hash` column. 0xFF never occurs in UTF-8, so it
+// keeps the values apart.
+pub(crate) fn rule_hash(ptype: &str, values: [&str; 6]) -> i64 {
+    let bytes = std::iter::once(ptype)
+        .chain(values.iter().copied())
+        .flat_map(|value| value.bytes().chain(std::iter::once(0xff)));
+    fnv1a(bytes) as i64
+}
+
+// The name every query uses for a table name given to a constructor, with
+// the whitespace around it, as left by config files, removed.
+pub(crate) fn canonical_table_name(name: impl Into<String>) -> Result<String, Error> {
+    let name = name.into();
+    let trimmed = name.trim();
+    let name = if trimmed.len() == name.len() {
+        name
+    } else {
+        trimmed.to_owned()
+    };
+    check_table_name(&name)?;
+    Ok(name)
+}
+
+// Table names are otherwise taken as they are, so they have to fit the
+// backend's limit themselves. SQLite has none.
+fn check_table_name(name: &str) -> Result<(), Error> {
+    let too_long = cfg!(not(feature = "sqlite")) && name.len() > MAX_IDENTIFIER_LEN;
+    if name.is_empty() || name.contains('\0') || too_long {
+        return Err(Error::InvalidTableName {
+            name: name.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn identifier(name: String) -> String {
+    if name.len() <= MAX_IDENTIFIER_LEN {
+        return name;
+    }
+
+    let hash = fnv1a(name.bytes());
+    let mut end = MAX_IDENTIFIER_LEN - 17;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}_{:016x}", &name[..end], hash)
+}
+
+// The unquoted name of the outbox kept for a rule table.
+pub(crate) fn outbox_name(table_name: &str) -> String {
+    identifier(format!("{}_outbox", table_name))
+}
+
+// Table names are quoted wherever they appear in SQL, so that mixed case and
+// reserved words are taken as they are.
+pub(crate) fn quote(identifier: &str) -> String {
+    Backend::CURRENT.quote(identifier)
+}
+
+// A section's rules have the section name as ptype, optionally followed by a
+// number (`p`, `p2`, ...). Other ptypes, like `global` or `q`, belong to
+// neither section.
+#[cfg(feature = "postgres")]
+fn in_section(sec: &str) -> String {
+    format!("ptype ~ '^{}[0-9]*$'", sec)
+}
+
+// Matched under the column's collation, like every other comparison.
+#[cfg(feature = "mysql")]
+fn in_section(sec: &str) -> String {
+    format!("ptype REGEXP '^{}[0-9]*$'", sec)
+}
+
+#[cfg(feature = "sqlite")]
+fn in_section(sec: &str) -> String {
+    format!("(ptype GLOB '{0}*' AND ptype NOT GLOB '{0}*[^0-9]*')", sec)
+}
+
+// The current time in the form the backend's timestamp columns take.
+pub(crate) const NOW: &str = Backend::CURRENT.now();
+
+// Timestamps are read as microseconds since the Unix epoch, which every
+// backend can produce without a date and time type on this side.
+#[cfg(feature = "postgres")]
+fn epoch_micros(column: &str) -> String {
+    format!("CAST(EXTRACT(EPOCH FROM {}) * 1000000 AS BIGINT)", column)
+}
+
+#[cfg(feature = "mysql")]
+fn epoch_micros(column: &str) -> String {
+    format!("CAST(UNIX_TIMESTAMP({}) * 1000000 AS SIGNED)", column)
+}
+
+// Stored as UTC text with millisecond precision.
+#[cfg(feature = "sqlite")]
+fn epoch_micros(column: &str) -> String {
+    format!(
+        "CAST(ROUND((julianday({}) - 2440587.5) * 86400000) AS INTEGER) * 1000",
+        column
+    )
+}
+
+// The timestamp for a bound number of microseconds since the Unix epoch.
+#[cfg(feature = "postgres")]
+fn from_epoch_micros(value: &str) -> String {
+    format!(
+        "to_timestamp(CAST({} AS DOUBLE PRECISION) / 1000000)",
+        value
+    )
+}
+
+// In the session's time zone, like CURRENT_TIMESTAMP.
+#[cfg(feature = "mysql")]
+fn from_epoch_micros(value: &str) -> String {
+    format!("FROM_UNIXTIME({} / 1000000)", value)
+}
+
+#[cfg(feature = "sqlite")]
+fn from_epoch_micros(value: &str) -> String {
+    format!(
+        "strftime('%Y-%m-%d %H:%M:%f', {} / 1000000.0, 'unixepoch')",
+        value
+    )
+}
+
+// Prefixed to the conditions of every statement that reads rules.
+fn live(soft_delete: bool) -> &'static str {
+    if soft_delete {
+        "deleted_at IS NULL AND "
+    } else {
+        ""
+    }
+}
+
+// Prefixed to the conditions of every load, after the scope.
+fn enabled(enabled_flag: bool) -> &'static str {
+    if enabled_flag {
+        "enabled AND "
+    } else {
+        ""
+    }
+}
+
+// Rules without an expiry never expire.
+fn unexpired(expiry: bool) -> String {
+    if expiry {
+        format!("(expires_at IS NULL OR expires_at > {}) AND ", NOW)
+    } else {
+        String::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct RemoveFiltered {
+    pub sql: Arc<str>,
+    // The rules the statement removes, by the same predicate.
+    pub select: Arc<str>,
+    // Value placeholders following the ptype one, one per column from the
+    // field index on.
+    pub values: usize,
+}
+
+#[cfg(feature = "postgres")]
+fn placeholder(n: usize) -> String {
+    format!("${}", n)
+}
+
+#[cfg(feature = "sqlite")]
+fn placeholder(n: usize) -> String {
+    format!("?{}", n)
+}
+
+#[cfg(feature = "mysql")]
+fn placeholder(_n: usize) -> String {
+    "?".to_owned()
+}
+
+impl Queries {
+    pub(crate) fn new(table_name: &str) -> Self {
+        Self::new_with_options(table_name, QueryOptions::default())
+    }
+
+    pub(crate) fn new_with_options(table_name: &str, options: QueryOptions) -> Self {
+        let QueryOptions {
+            legacy_nulls,
+            soft_delete,
+            revisions,
+            enabled_flag,
+            expiry,
+            modified_by,
+            descriptions,
+            timestamps,
+            rule_hash,
+            ..
+        } = options;
+        let tenant = options.tenant.is_some();
+        // The tenant takes the first placeholder of every statement it
+        // scopes, the statement's own ones follow.
+        let p = |n: usize| placeholder(n + usize::from(tenant));
+        let table = quote(table_name);
+
+        // The conditions keeping every statement to the adapter's own rules.
+        let tenant_scope = if tenant {
+            format!("tenant_id = {} AND ", placeholder(1))
+        } else {
+            String::new()
+        };
+        let scope = format!("{}{}", tenant_scope, live(soft_delete));
+        let scoped = |conditions: &str| format!("{}{}", scope, conditions);
+        let scope_where = if scope.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", scope.trim_end_matches(" AND "))
+        };
+
+        let touch = if timestamps {
+            format!(", updated_at = {}", NOW)
+        } else {
+            String::new()
+        };
+        let removal = if soft_delete {
+            format!("UPDATE {} SET deleted_at = {}{}", table, NOW, touch)
+        } else {
+            format!("DELETE FROM {}", table)
+        };
+        let clear = format!("{}{}", removal, scope_where);
+        // Disabled rules are not part of the model and outlive its saves.
+        let save_clear = if enabled_flag {
+            format!("{} WHERE {}enabled", removal, scope)
+        } else {
+            clear.clone()
+        };
+        let removal = format!("{} WHERE ", removal);
+        let section_clear = [format!("NOT {}", in_section("g")), in_section("g")].map(|section| {
+            format!("{}{}{}{}", removal, scope, enabled(enabled_flag), section).into()
+        });
+        let remove_where = format!("{}{}", removal, scope);
+
+        let columns = [0, 1, 2, 3, 4, 5].map(|column| {
+            if legacy_nulls {
+                format!("COALESCE(v{}, '')", column)
+            } else {
+                format!("v{}", column)
+            }
+        });
+
+        // Ids are read as 64-bit, also from Postgres tables that still have a
+        // 32-bit key, and UUIDs as text.
+        #[cfg(feature = "postgres")]
+        let id = match options.id_type {
+            IdType::BigInt => "CAST(id AS BIGINT) AS id",
+            IdType::Uuid => "CAST(id AS TEXT) AS id",
+        };
+        #[cfg(any(feature = "sqlite", feature = "mysql"))]
+        let id = "id";
+
+        let select_list = format!(
+            "{}, ptype, {}{}{}",
+            id,
+            columns
+                .iter()
+                .enumerate()
+                .map(|(column, expr)| if legacy_nulls {
+                    format!("{} AS v{}", expr, column)
+                } else {
+                    expr.clone()
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+            if modified_by { ", modified_by" } else { "" },
+            if descriptions { ", description" } else { "" }
+        );
+        let table_select = format!("SELECT {} FROM {}", select_list, table);
+        let select = format!("{}{}", table_select, scope_where);
+        // Every row a load may return.
+        let load_conditions = format!("{}{}", enabled(enabled_flag), unexpired(expiry));
+        let load_scope = format!("{}{}", scope, load_conditions);
+        let load_select = if load_scope.is_empty() {
+            table_select.clone()
+        } else {
+            format!(
+                "{} WHERE {}",
+                table_select,
+                load_scope.trim_end_matches(" AND ")
+            )
+        };
+
+        // The tenant comes first, then the rule and its hash, the statement's
+        // own column and the actor.
+        let insert_into = |extra: Option<(&str, String)>| {
+            let mut columns = Vec::new();
+            let mut values = Vec::new();
+            if tenant {
+                columns.push("tenant_id");
+                values.push(placeholder(1));
+            }
+            columns.extend(["ptype", "v0", "v1", "v2", "v3", "v4", "v5"].iter());
+            values.extend((1..=7).map(&p));
+            if rule_hash {
+                columns.push("rule_hash");
+                values.push(p(8));
+            }
+            if let Some((column, value)) = extra {
+                columns.push(column);
+                values.push(value);
+            }
+            if modified_by {
+                columns.push("modified_by");
+                values.push(p(values.len() - usize::from(tenant) + 1));
+            }
+            format!(
+                "INSERT INTO {} ( {} ) VALUES ( {} )",
+                table,
+                columns.join(", "),
+                values.join(", ")
+            )
+        };
+        let own = 8 + usize::from(rule_hash);
+        let insert = insert_into(None);
+        let insert_with_metadata = insert_into(Some(("metadata", p(own))));
+        let insert_with_enabled = insert_into(Some(("enabled", p(own))));
+        let insert_with_expiry = insert_into(Some(("expires_at", from_epoch_micros(&p(own)))));
+
+        let rule_predicate = format!(
+            "ptype = {} AND {}",
+            options.compared(Column::Ptype, p(1)),
+            columns
+                .iter()
+                .zip(Column::VALUES.iter())
+                .enumerate()
+                .map(|(i, (expr, &column))| {
+                    format!("{} = {}", expr, options.compared(column, p(i + 2)))
+                })
+                .collect::<Vec<String>>()
+                .join(" AND ")
+        );
+
+        // The hash is bound after the rule, like on insert.
+        let hashed_predicate = if options.hash_lookups() {
+            format!("{} AND rule_hash = {}", rule_predicate, p(8))
+        } else {
+            rule_predicate.clone()
+        };
+
+        let remove = format!("{}{}", remove_where, hashed_predicate);
+
+        // Updates set their own columns first and then the time and the
+        // actor, all ahead of the conditions in the text, which MySQL binds
+        // by.
+        let stamp = |n: usize| {
+            if modified_by {
+                format!("{}, modified_by = {}", touch, p(n))
+            } else {
+                touch.clone()
+            }
+        };
+        // Only rows whose flag changes are counted.
+        let enable_rule = format!(
+            "UPDATE {} SET enabled = TRUE{} WHERE {}",
+            table,
+            stamp(8),
+            scoped(&format!("NOT enabled AND {}", rule_predicate))
+        );
+        let disable_rule = format!(
+            "UPDATE {} SET enabled = FALSE{} WHERE {}",
+            table,
+            stamp(8),
+            scoped(&format!("enabled AND {}", rule_predicate))
+        );
+        let set_description = format!(
+            "UPDATE {} SET description = {}{} WHERE {}",
+            table,
+            p(8),
+            stamp(9),
+            scoped(&rule_predicate)
+        );
+
+        let metadata = format!(
+            "SELECT metadata FROM {} WHERE {} LIMIT 1",
+            table,
+            scoped(&rule_predicate)
+        );
+
+        #[cfg(feature = "postgres")]
+        let exists = format!(
+            "SELECT EXISTS(SELECT 1 FROM {} WHERE {}{})",
+            table,
+            scoped(&unexpired(expiry)),
+            hashed_predicate
+        );
+
+        #[cfg(any(feature = "sqlite", feature = "mysql"))]
+        let exists = format!(
+            "SELECT 1 FROM {} WHERE {}{} LIMIT 1",
+            table,
+            scoped(&unexpired(expiry)),
+            hashed_predicate
+        );
+
+        // One statement per field index. Unset values are bound as NULL and
+        // match anything through COALESCE.
+        let remove_filtered = [0, 1, 2, 3, 4, 5].map(|field_index| {
+            let predicates = columns[field_index..]
+                .iter()
+                .zip(Column::VALUES[field_index..].iter())
+                .enumerate()
+                .map(|(i, (expr, &column))| {
+                    format!(
+                        "{0} = COALESCE({1}, {0})",
+                        expr,
+                        options.compared(column, p(i + 2))
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(" AND ");
+
+            let predicate = format!(
+                "ptype = {} AND {}",
+                options.compared(Column::Ptype, p(1)),
+                predicates
+            );
+            RemoveFiltered {
+                sql: format!("{}{}", remove_where, predicate).into(),
+                select: format!("{} WHERE {}{}", table_select, scope, predicate).into(),
+                values: 6 - field_index,
+            }
+        });
+
+        let revision_table = quote(&identifier(format!("{}_revision", table_name)));
+        let outbox_table = match &options.outbox {
+            Some(outbox) => quote(outbox),
+            None => quote(&outbox_name(table_name)),
+        };
+
+        #[cfg(feature = "postgres")]
+        let seed_revision = format!(
+            "INSERT INTO {} (id, revision) VALUES (1, 0) ON CONFLICT DO NOTHING",
+            revision_table
+        );
+        #[cfg(feature = "mysql")]
+        let seed_revision = format!(
+            "INSERT IGNORE INTO {} (id, revision) VALUES (1, 0)",
+            revision_table
+        );
+        #[cfg(feature = "sqlite")]
+        let seed_revision = format!(
+            "INSERT OR IGNORE INTO {} (id, revision) VALUES (1, 0)",
+            revision_table
+        );
+
+        let applied_migrations = format!(
+            "SELECT version FROM {} WHERE table_name = {} ORDER BY version",
+            MIGRATIONS_TABLE,
+            placeholder(1)
+        );
+        let record_migration = format!(
+            "INSERT INTO {} (table_name, version, description) VALUES ({}, {}, {})",
+            MIGRATIONS_TABLE,
+            placeholder(1),
+            placeholder(2),
+            placeholder(3)
+        );
+
+        #[cfg(any(test, feature = "test-util"))]
+        let forget_migrations = format!(
+            "DELETE FROM {} WHERE table_name = {}",
+            MIGRATIONS_TABLE,
+            placeholder(1)
+        );
+
+        Self {
+            table_name: table_name.into(),
+            unhashed: format!(
+                "{} WHERE rule_hash IS NULL ORDER BY id LIMIT {}",
+                table_select, HASH_BACKFILL_BATCH
+            )
+            .into(),
+            backfill_hash: format!(
+                "UPDATE {} SET rule_hash = {} WHERE rule_hash IS NULL AND ptype = {} AND {}",
+                table,
+                placeholder(1),
+                placeholder(2),
+                columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| format!("{} = {}", column, placeholder(i + 3)))
+                    .collect::<Vec<String>>()
+                    .join(" AND ")
+            )
+            .into(),
+            applied_migrations: applied_migrations.into(),
+            record_migration: record_migration.into(),
+            #[cfg(any(test, feature = "test-util"))]
+            forget_migrations: forget_migrations.into(),
+            seed_revision: seed_revision.into(),
+            current_revision: format!("SELECT revision FROM {} WHERE id = 1", revision_table)
+                .into(),
+            bump_revision: if revisions {
+                Some(
+                    format!(
+                        "UPDATE {} SET revision = revision + 1 WHERE id = 1",
+                        revision_table
+                    )
+                    .into(),
+                )
+            } else {
+                None
+            },
+            revision_table: revision_table.into(),
+            poll_outbox: format!(
+                "SELECT id, op, payload, {} FROM {} ORDER BY id LIMIT {}",
+                epoch_micros("created_at"),
+                outbox_table,
+                placeholder(1)
+            )
+            .into(),
+            insert_outbox: options.outbox.as_ref().map(|_| {
+                format!(
+                    "INSERT INTO {} (op, payload) VALUES ({}, {})",
+                    outbox_table,
+                    placeholder(1),
+                    placeholder(2)
+                )
+                .into()
+            }),
+            outbox_table: outbox_table.into(),
+            #[cfg(feature = "postgres")]
+            rule_columns: format!("ptype, {}", columns.join(", ")).into(),
+            unique_constraint: identifier(format!("unique_key_sqlx_adapter_{}", table_name)).into(),
+            insert: insert.into(),
+            insert_with_metadata: insert_with_metadata.into(),
+            insert_with_enabled: insert_with_enabled.into(),
+            insert_with_expiry: insert_with_expiry.into(),
+            metadata: metadata.into(),
+            enable_rule: enable_rule.into(),
+            disable_rule: disable_rule.into(),
+            list_disabled: format!(
+                "{} WHERE {} ORDER BY id",
+                table_select,
+                scoped("NOT enabled")
+            )
+            .into(),
+            set_description: set_description.into(),
+            described: format!(
+                "{} WHERE {}",
+                table_select,
+                scoped("description IS NOT NULL")
+            )
+            .into(),
+            remove: remove.into(),
+            exists: exists.into(),
+            remove_filtered,
+            #[cfg(feature = "postgres")]
+            save_lock: fnv1a(table_name.bytes()) as i64,
+            #[cfg(feature = "mysql")]
+            save_lock: identifier(format!("sqlx_adapter_save_{}", table_name)).into(),
+            remove_ptype: format!(
+                "{}ptype = {}",
+                remove_where,
+                options.compared(Column::Ptype, p(1))
+            )
+            .into(),
+            // Truncating would take the other tenants' rules along.
+            #[cfg(feature = "postgres")]
+            truncate: if soft_delete || tenant || enabled_flag {
+                None
+            } else {
+                Some(format!("TRUNCATE TABLE {} RESTART IDENTITY", table).into())
+            },
+            load_by_id: format!("{} ORDER BY id", load_select).into(),
+            export: format!("{} ORDER BY ptype, id", load_select).into(),
+            // A single statement, so that both sections come from the same
+            // snapshot.
+            load_grouping_first: format!(
+                "{} ORDER BY CASE WHEN {} THEN 0 ELSE 1 END, id",
+                load_select,
+                in_section("g")
+            )
+            .into(),
+            load_unsectioned: format!(
+                "{} WHERE {}NOT {} AND NOT {} ORDER BY id",
+                table_select,
+                load_scope,
+                in_section("g"),
+                in_section("p")
+            )
+            .into(),
+            load_timestamped: format!(
+                "SELECT {}, {} AS created_at, {} AS updated_at FROM {}{} ORDER BY id",
+                select_list,
+                epoch_micros("created_at"),
+                epoch_micros("updated_at"),
+                table,
+                scope_where
+            )
+            .into(),
+            list_page: format!("{} ORDER BY id LIMIT {} OFFSET {}", select, p(1), p(2)).into(),
+            stream: format!("{} ORDER BY id", select).into(),
+            stream_ptype: format!(
+                "{} WHERE {}ptype = {} ORDER BY id",
+                table_select,
+                scope,
+                p(1)
+            )
+            .into(),
+            purge_deleted: format!(
+                "DELETE FROM {} WHERE {}deleted_at IS NOT NULL AND {} < {}",
+                table,
+                tenant_scope,
+                epoch_micros("deleted_at"),
+                p(1)
+            )
+            .into(),
+            purge_expired: format!(
+                "DELETE FROM {} WHERE {}expires_at <= {}",
+                table, tenant_scope, NOW
+            )
+            .into(),
+            load_conditions: load_conditions.into(),
+            removal: removal.into(),
+            clear: clear.into(),
+            save_clear: save_clear.into(),
+            section_clear,
+            select: table_select.into(),
+            columns: columns.map(Into::into),
+            table: table.into(),
+            options,
+        }
+    }
+
+    pub(crate) fn tenant(&self) -> Option<&str> {
+        self.options.tenant.as_deref()
+    }
+
+    // None unless the column is written, and the inner None writes NULL.
+    pub(crate) fn actor(&self) -> Option<Option<&str>> {
+        if self.options.modified_by {
+            Some(self.options.actor.as_deref())
+        } else {
+            None
+        }
+    }
+
+    // The scope for statements built with a QueryBuilder, which numbers the
+    // placeholders itself. Followed by a condition.
+    pub(crate) fn push_scope(&self, builder: &mut QueryBuilder<'_, Db>) {
+        if let Some(tenant) = self.tenant() {
+            builder
+                .push("tenant_id = ")
+                .push_bind(tenant.to_owned())
+                .push(" AND ");
+        }
+        builder.push(live(self.options.soft_delete));
+    }
+
+    pub(crate) fn section_clear(&self, section: Section) -> &str {
+        match section {
+            Section::P => &self.section_clear[0],
+            Section::G => &self.section_clear[1],
+        }
+    }
+
+    // The default table with the default options, the one the statements of
+    // the `compile-checked` feature were checked against.
+    #[cfg(all(feature = "compile-checked", feature = "postgres"))]
+    pub(crate) fn is_static(&self) -> bool {
+        &*self.table_name == "casbin_rule" && self.options == QueryOptions::default()
+    }
+
+    pub(crate) fn load(&self, order: LoadOrder) -> &str {
+        match order {
+            LoadOrder::Insertion => &self.load_by_id,
+            LoadOrder::GroupingFirst => &self.load_grouping_first,
+        }
+    }
+
+    // A bound value compared with the column, for statements built with a
+    // QueryBuilder.
+    pub(crate) fn push_compared<'f>(
+        &self,
+        builder: &mut QueryBuilder<'f, Db>,
+        column: Column,
+        value: &'f str,
+    ) {
+        if self.options.is_case_insensitive(column) {
+            builder.push("CAST(").push_bind(value).push(" AS CITEXT)");
+        } else {
+            builder.push_bind(value);
+        }
+    }
+
+    // Filtered loads are built per section and filter. Filter values are
+    // literals compared with `=`, so that `%` and `_` in stored values mean
+    // nothing to SQL and the unique index can serve the lookup. Only empty
+    // values match anything, by dropping their predicate.
+    pub(crate) fn push_load_filtered<'f>(
+        &self,
+        builder: &mut QueryBuilder<'f, Db>,
+        sec: &str,
+        values: &[&'f str],
+    ) {
+        builder.push(format!("{} WHERE ", self.select));
+        self.push_scope(builder);
+        builder.push(&*self.load_conditions);
+        builder.push(in_section(sec));
+
+        for ((expr, &column), &value) in self.columns.iter().zip(Column::VALUES.iter()).zip(values)
+        {
+            if value.is_empty() {
+                continue;
+            }
+
+            self.push_compared(builder.push(format!(" AND {} = ", expr)), column, value);
+        }
+
+        builder.push(" ORDER BY id");
+    }
+}
@@ -0,0 +1,61 @@
+/// What became of one rule in `SqlxAdapter::add_policies_report`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleOutcome {
+    Inserted,
+    /// The rule was already stored, or came earlier in the same batch.
+    Duplicate,
+    /// The rule was rejected, with the validation or database message.
+    Failed(String),
+}
+
+/// Per-rule outcomes of `SqlxAdapter::add_policies_report`, in the order the
+/// rules were given.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchReport {
+    outcomes: Vec<RuleOutcome>,
+}
+
+impl BatchReport {
+    pub(crate) fn push(&mut self, outcome: RuleOutcome) {
+        self.outcomes.push(outcome);
+    }
+
+    pub fn outcomes(&self) -> &[RuleOutcome] {
+        &self.outcomes
+    }
+
+    /// The index and message of every rule that failed.
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.outcomes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, outcome)| match outcome {
+                RuleOutcome::Failed(message) => Some((index, message.as_str())),
+                _ => None,
+            })
+    }
+}
+
+/// The counts of `SqlxAdapter::copy_to`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopyReport {
+    inserted: u64,
+    skipped: u64,
+}
+
+impl CopyReport {
+    pub(crate) fn add(&mut self, inserted: u64, skipped: u64) {
+        self.inserted += inserted;
+        self.skipped += skipped;
+    }
+
+    /// The rules stored in the target.
+    pub fn inserted(&self) -> u64 {
+        self.inserted
+    }
+
+    /// The rules left out because the target held them already.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
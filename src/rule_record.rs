@@ -0,0 +1,69 @@
+use crate::{models::CasbinRule, policy_rule::PolicyRule};
+
+/// A stored rule as listed by `SqlxAdapter::list_rules`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CasbinRuleRecord {
+    /// The primary key, a decimal integer or a UUID depending on the table's
+    /// `IdType`.
+    pub id: String,
+    /// Serialized as the record's `ptype` and `values` fields.
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub rule: PolicyRule,
+    /// Who last added or changed the rule, read with
+    /// `SqlxAdapter::with_modified_by`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub modified_by: Option<String>,
+    /// What the rule is for, read with `SqlxAdapter::with_descriptions`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub description: Option<String>,
+}
+
+impl CasbinRuleRecord {
+    pub fn new<I: Into<String>>(id: I, rule: PolicyRule) -> Self {
+        Self {
+            id: id.into(),
+            rule,
+            modified_by: None,
+            description: None,
+        }
+    }
+
+    pub(crate) fn from_row(row: CasbinRule) -> Self {
+        Self {
+            modified_by: row.modified_by,
+            description: row.description,
+            ..Self::new(
+                row.id.to_string(),
+                PolicyRule::stored(
+                    row.ptype,
+                    vec![row.v0, row.v1, row.v2, row.v3, row.v4, row.v5],
+                ),
+            )
+        }
+    }
+}
+
+/// The record's values in the `Vec<String>` form casbin uses.
+impl From<CasbinRuleRecord> for Vec<String> {
+    fn from(record: CasbinRuleRecord) -> Self {
+        record.rule.into_parts().1
+    }
+}
+
+pub(crate) fn trim_trailing_empty(mut values: Vec<String>) -> Vec<String> {
+    while let Some(last) = values.last() {
+        if last.is_empty() {
+            values.pop();
+        } else {
+            break;
+        }
+    }
+    values
+}
@@ -0,0 +1,49 @@
+use crate::{error::Error, id_type::IdType};
+
+/// A column the adapter needs, stored with a type it cannot read or write.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncompatibleColumn {
+    pub column: &'static str,
+    /// The type as the database names it.
+    pub data_type: String,
+}
+
+/// How the table compares with the columns the adapter expects, see
+/// `SqlxAdapter::validate_schema`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaReport {
+    /// The kind of key `id` holds, None when the column is missing or
+    /// incompatible.
+    pub id_type: Option<IdType>,
+    /// Columns out of `id`, `ptype` and `v0` to `v5` that the table lacks.
+    pub missing: Vec<&'static str>,
+    pub incompatible: Vec<IncompatibleColumn>,
+    /// Rule columns that allow NULL, which only `with_legacy_nulls` reads.
+    pub nullable: Vec<&'static str>,
+    /// Columns neither required nor added by one of the adapter's
+    /// migrations. They need a default for inserts to succeed.
+    pub extra: Vec<String>,
+}
+
+impl SchemaReport {
+    /// Whether the adapter can read and write the table, which may still
+    /// need `with_legacy_nulls`.
+    pub fn is_compatible(&self) -> bool {
+        self.missing.is_empty() && self.incompatible.is_empty()
+    }
+
+    pub(crate) fn error(&self) -> Option<Error> {
+        if let Some(column) = self.missing.first() {
+            return Some(Error::SchemaMismatch {
+                column,
+                found: None,
+            });
+        }
+        self.incompatible
+            .first()
+            .map(|incompatible| Error::SchemaMismatch {
+                column: incompatible.column,
+                found: Some(incompatible.data_type.clone()),
+            })
+    }
+}
@@ -0,0 +1,26 @@
+/// A section of the policy, see `SqlxAdapter::save_section_policy`. Rules
+/// of ptypes outside the g section, like `global`, belong to the p section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Section {
+    P,
+    G,
+}
+
+impl Section {
+    /// The section of `ptype`: g for `g`, `g2` and so on, like the ptypes
+    /// the SQL sections match, and p for any other.
+    pub fn of(ptype: &str) -> Self {
+        match ptype.strip_prefix('g') {
+            Some(rest) if rest.bytes().all(|byte| byte.is_ascii_digit()) => Section::G,
+            _ => Section::P,
+        }
+    }
+
+    /// The `sec` argument casbin's `Adapter` calls take next to the ptype.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Section::P => "p",
+            Section::G => "g",
+        }
+    }
+}
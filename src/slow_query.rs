@@ -0,0 +1,43 @@
+use crate::metrics_hook::{Operation, Outcome};
+use std::time::Duration;
+
+// The operations that take at least `threshold`, logged as warnings, see
+// `SqlxAdapter::with_slow_query_threshold`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SlowQueryLog {
+    pub threshold: Duration,
+}
+
+impl SlowQueryLog {
+    pub(crate) fn report(
+        &self,
+        op: Operation,
+        table: &str,
+        duration: Duration,
+        outcome: &Outcome,
+        values: Option<&str>,
+    ) {
+        if duration < self.threshold {
+            return;
+        }
+
+        let (outcome, rows) = match outcome {
+            Outcome::Success { rows } => ("success", *rows),
+            Outcome::Failure(class) => (class.as_str(), None),
+        };
+        let duration_ms = duration.as_millis() as u64;
+        log::warn!(
+            target: "sqlx_adapter::slow_query",
+            operation = op.as_str(),
+            table = table,
+            duration_ms = duration_ms,
+            rows = rows,
+            outcome = outcome,
+            values = values;
+            "slow policy operation {} on {} took {}ms",
+            op.as_str(),
+            table,
+            duration_ms
+        );
+    }
+}
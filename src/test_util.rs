@@ -0,0 +1,368 @@
+//! Adapters on throwaway tables for tests, behind the `test-util` feature.
+
+use crate::{
+    actions as adapter, queries::Queries, Error, ImportMode, ImportSummary, PolicyRule,
+    PolicyStore, Section, SqlxAdapter,
+};
+use async_trait::async_trait;
+use casbin::{Error as CasbinError, Filter, Model, Result};
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+// Tables of adapters dropped without `cleanup`, dropped in turn by the next
+// `TestAdapter::new` or `cleanup`.
+static ABANDONED: Mutex<Vec<Queries>> = Mutex::new(Vec::new());
+
+/// A `SqlxAdapter` on a table of its own, with a random name, that it
+/// derefs to. Call `cleanup` at the end of the test to drop the table.
+/// Drop cannot wait for the database, so the tables of adapters dropped
+/// without it, say by a failed assertion, are dropped by the next
+/// `TestAdapter::new` or `cleanup` of the process.
+///
+/// It takes any pool, such as the one `#[sqlx::test]` hands the test when
+/// sqlx's `migrate` feature is on:
+///
+/// ```no_run
+/// # #[cfg(feature = "postgres")]
+/// async fn alice_reads_data1(pool: sqlx::PgPool) -> casbin::Result<()> {
+///     use sqlx_adapter::{PolicyRule, TestAdapter};
+///
+///     let adapter = TestAdapter::new(pool).await?;
+///     adapter.seed(&[&["p", "alice", "data1", "read"]]).await?;
+///     let rule = PolicyRule::new("p", vec!["alice".into(), "data1".into(), "read".into()])?;
+///     assert!(adapter.has_policy(&rule).await?);
+///     adapter.cleanup().await
+/// }
+/// ```
+pub struct TestAdapter {
+    adapter: SqlxAdapter,
+    cleaned: bool,
+}
+
+impl TestAdapter {
+    pub async fn new(pool: adapter::ConnectionPool) -> Result<Self> {
+        drop_abandoned(&pool).await?;
+
+        let table_name = format!("casbin_rule_test_{:016x}", random());
+        let adapter = SqlxAdapter::new_with_pool_and_table_name(pool, &table_name).await?;
+        Ok(Self {
+            adapter,
+            cleaned: false,
+        })
+    }
+
+    /// Sets options through the `with_*` methods of `SqlxAdapter`, which
+    /// take it by value:
+    /// `TestAdapter::new(pool).await?.configure(|a| a.with_trim_values(true))`.
+    pub fn configure(mut self, f: impl FnOnce(SqlxAdapter) -> SqlxAdapter) -> Self {
+        self.adapter = f(self.adapter.clone());
+        self
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.adapter.queries.table_name
+    }
+
+    /// Stores rules given as `[ptype, v0, v1, ...]` in one transaction,
+    /// skipping those already stored.
+    pub async fn seed(&self, rules: &[&[&str]]) -> Result<ImportSummary> {
+        let policies = rules
+            .iter()
+            .filter_map(|rule| rule.split_first())
+            .map(|(ptype, values)| {
+                let values = values.iter().map(|value| value.to_string()).collect();
+                (ptype.to_string(), values)
+            })
+            .collect();
+        self.adapter
+            .import_policies(policies, ImportMode::Merge, |_, err| err)
+            .await
+    }
+
+    /// Drops the table, and those of adapters dropped without `cleanup`.
+    pub async fn cleanup(mut self) -> Result<()> {
+        self.cleaned = true;
+        drop_abandoned(&self.adapter.pool).await?;
+        adapter::drop_table(&self.adapter.pool, &self.adapter.queries).await
+    }
+}
+
+impl Deref for TestAdapter {
+    type Target = SqlxAdapter;
+
+    fn deref(&self) -> &SqlxAdapter {
+        &self.adapter
+    }
+}
+
+impl DerefMut for TestAdapter {
+    fn deref_mut(&mut self) -> &mut SqlxAdapter {
+        &mut self.adapter
+    }
+}
+
+impl Drop for TestAdapter {
+    fn drop(&mut self) {
+        if !self.cleaned {
+            if let Ok(mut abandoned) = ABANDONED.lock() {
+                abandoned.push(self.adapter.queries.clone());
+            }
+        }
+    }
+}
+
+async fn drop_abandoned(pool: &adapter::ConnectionPool) -> Result<()> {
+    let abandoned = match ABANDONED.lock() {
+        Ok(mut abandoned) => std::mem::take(&mut *abandoned),
+        Err(_) => return Ok(()),
+    };
+    for queries in abandoned {
+        adapter::drop_table(pool, &queries).await?;
+    }
+    Ok(())
+}
+
+// Unique within the process, and random across processes through the
+// randomly keyed hasher.
+fn random() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+/// A `PolicyStore` keeping the rules in memory, for unit tests of code that
+/// takes a `SqlxAdapter` but should not need a database:
+///
+/// ```
+/// # async fn test() -> casbin::Result<()> {
+/// use sqlx_adapter::casbin::Adapter;
+/// use sqlx_adapter::{InMemoryPolicyStore, SqlxAdapter};
+/// use std::sync::Arc;
+///
+/// let store = Arc::new(InMemoryPolicyStore::new());
+/// let mut adapter = SqlxAdapter::new_with_store(store.clone()).await;
+/// let rule = vec!["alice".to_owned(), "data1".to_owned(), "read".to_owned()];
+/// adapter.add_policy("p", "p", rule.clone()).await?;
+/// assert_eq!(vec![("p".to_owned(), rule)], store.rules());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemoryPolicyStore {
+    // Values are padded to six like in the tables, so that rules differing
+    // only by trailing empty values are the same rule.
+    rules: Mutex<Vec<(String, Vec<String>)>>,
+}
+
+impl InMemoryPolicyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The stored rules as ptype and values, in the order they were added.
+    pub fn rules(&self) -> Vec<(String, Vec<String>)> {
+        self.lock()
+            .iter()
+            .map(|(ptype, values)| (ptype.clone(), trimmed(values)))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<(String, Vec<String>)>> {
+        self.rules.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Adds the rules that `filter` lets through, by section and values, to
+    // `m`.
+    fn load_where(&self, m: &mut dyn Model, filter: impl Fn(Section, &[String]) -> bool) -> u64 {
+        let mut loaded = 0;
+        for (ptype, values) in self.rules() {
+            let sec = match ptype.chars().next() {
+                Some(c) => &ptype[..c.len_utf8()],
+                None => continue,
+            };
+            if !filter(Section::of(&ptype), &values) {
+                continue;
+            }
+            loaded += 1;
+            if let Some(assertion) = m
+                .get_mut_model()
+                .get_mut(sec)
+                .and_then(|assertions| assertions.get_mut(&ptype))
+            {
+                assertion.get_mut_policy().insert(values);
+            }
+        }
+        loaded
+    }
+}
+
+#[async_trait]
+impl PolicyStore for InMemoryPolicyStore {
+    async fn load(&self, m: &mut dyn Model) -> Result<u64> {
+        Ok(self.load_where(m, |_, _| true))
+    }
+
+    async fn load_filtered(&self, m: &mut dyn Model, f: &Filter<'_>) -> Result<u64> {
+        Ok(self.load_where(m, |section, values| {
+            let wanted = match section {
+                Section::P => &f.p,
+                Section::G => &f.g,
+            };
+            wanted
+                .iter()
+                .zip(values)
+                .all(|(wanted, value)| wanted.is_empty() || wanted == value)
+        }))
+    }
+
+    async fn add(&self, ptype: &str, rule: Vec<String>) -> Result<bool> {
+        let rule = match padded(ptype, &rule) {
+            Some(rule) => rule,
+            None => return Ok(false),
+        };
+        let mut rules = self.lock();
+        if rules.contains(&rule) {
+            return Err(already_exists(None));
+        }
+        rules.push(rule);
+        Ok(true)
+    }
+
+    async fn add_many(&self, ptype: &str, rules: Vec<Vec<String>>) -> Result<bool> {
+        let new_rules = rules
+            .iter()
+            .filter_map(|rule| padded(ptype, rule))
+            .collect::<Vec<_>>();
+        let mut rules = self.lock();
+        // Repeats within the batch are reported before rules stored earlier.
+        let index = duplicate_index(&new_rules)
+            .or_else(|| new_rules.iter().position(|rule| rules.contains(rule)));
+        if index.is_some() {
+            return Err(already_exists(index));
+        }
+        rules.extend(new_rules);
+        Ok(true)
+    }
+
+    async fn remove(&self, ptype: &str, rule: Vec<String>) -> Result<bool> {
+        let rule = (ptype.to_owned(), pad(&rule));
+        let mut rules = self.lock();
+        let before = rules.len();
+        rules.retain(|stored| *stored != rule);
+        Ok(rules.len() < before)
+    }
+
+    async fn remove_many(&self, ptype: &str, rules: Vec<Vec<String>>) -> Result<bool> {
+        let removed = rules
+            .iter()
+            .map(|rule| (ptype.to_owned(), pad(rule)))
+            .collect::<HashSet<_>>();
+        let mut rules = self.lock();
+        if !removed.iter().all(|rule| rules.contains(rule)) {
+            return Ok(false);
+        }
+        rules.retain(|stored| !removed.contains(stored));
+        Ok(true)
+    }
+
+    async fn remove_filtered(
+        &self,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        // Rules have six values, none of them past v5.
+        if field_index > 5 {
+            return Ok(false);
+        }
+        let mut rules = self.lock();
+        let before = rules.len();
+        rules.retain(|(stored_ptype, values)| {
+            let matches = stored_ptype == ptype
+                && field_values
+                    .iter()
+                    .zip(&values[field_index..])
+                    .all(|(wanted, value)| wanted.is_empty() || wanted == value);
+            !matches
+        });
+        Ok(rules.len() < before)
+    }
+
+    async fn import(&self, rules: Vec<PolicyRule>, strict: bool) -> Result<u64> {
+        let new_rules = rules
+            .iter()
+            .filter_map(|rule| padded(rule.ptype(), rule.values()))
+            .collect::<Vec<_>>();
+        let mut rules = self.lock();
+        if strict {
+            let index = duplicate_index(&new_rules)
+                .or_else(|| new_rules.iter().position(|rule| rules.contains(rule)));
+            if index.is_some() {
+                return Err(already_exists(index));
+            }
+        }
+        let mut imported = 0;
+        for rule in new_rules {
+            if !rules.contains(&rule) {
+                rules.push(rule);
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
+
+    async fn save(&self, m: &dyn Model) -> Result<()> {
+        let new_rules = SqlxAdapter::model_policies(m)
+            .filter_map(|(ptype, rule)| padded(ptype, rule))
+            .collect::<Vec<_>>();
+        if let Some(index) = duplicate_index(&new_rules) {
+            return Err(already_exists(Some(index)));
+        }
+        *self.lock() = new_rules;
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<()> {
+        self.lock().clear();
+        Ok(())
+    }
+}
+
+// Rules without a ptype or values are not stored, like by the tables.
+fn padded(ptype: &str, rule: &[String]) -> Option<(String, Vec<String>)> {
+    if ptype.trim().is_empty() || rule.is_empty() {
+        return None;
+    }
+    Some((ptype.to_owned(), pad(rule)))
+}
+
+fn pad(rule: &[String]) -> Vec<String> {
+    adapter::normalize_casbin_rule(rule)
+        .iter()
+        .map(|value| value.to_string())
+        .collect()
+}
+
+fn trimmed(values: &[String]) -> Vec<String> {
+    let len = values
+        .iter()
+        .rposition(|value| !value.is_empty())
+        .map_or(0, |last| last + 1);
+    values[..len].to_vec()
+}
+
+fn duplicate_index(rules: &[(String, Vec<String>)]) -> Option<usize> {
+    let mut seen = HashSet::new();
+    rules.iter().position(|rule| !seen.insert(rule))
+}
+
+fn already_exists(index: Option<usize>) -> CasbinError {
+    CasbinError::from(Error::AlreadyExists { index })
+}
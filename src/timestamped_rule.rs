@@ -0,0 +1,15 @@
+use std::time::SystemTime;
+
+use crate::policy_rule::PolicyRule;
+
+/// A stored rule with the times it was added and last changed, as listed by
+/// `SqlxAdapter::rules_with_timestamps`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimestampedRule {
+    /// The primary key, a decimal integer or a UUID depending on the table's
+    /// `IdType`.
+    pub id: String,
+    pub rule: PolicyRule,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+}
@@ -0,0 +1,174 @@
+//! Drives the casbin-sqlx binary against a SQLite file of its own.
+#![cfg(all(feature = "cli", feature = "sqlite"))]
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+struct Database(PathBuf);
+
+impl Database {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "casbin_sqlx_cli_{}_{}.db",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        Database(path)
+    }
+
+    fn url(&self) -> String {
+        format!("sqlite://{}?mode=rwc", self.0.display())
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_casbin-sqlx"))
+            .env("DATABASE_URL", self.url())
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    // Runs `sql` on the database, behind the binary's back.
+    fn execute(&self, sql: &str) {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let pool = sqlx::SqlitePool::connect(&self.url()).await.unwrap();
+            sqlx::query(sql).execute(&pool).await.unwrap();
+            pool.close().await;
+        });
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn add_list_remove() {
+    let db = Database::new("rules");
+
+    let output = db.run(&["add", "p", "alice", "data1", "read"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("added\n", stdout(&output));
+    assert_eq!(Some(0), db.run(&["add", "g", "bob", "alice"]).status.code());
+    // A second add is refused by the unique key.
+    let output = db.run(&["add", "p", "alice", "data1", "read"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: "));
+
+    let output = db.run(&["list"]);
+    assert_eq!(Some(0), output.status.code());
+    let listed = stdout(&output);
+    let lines = listed.lines().collect::<Vec<_>>();
+    assert_eq!(3, lines.len());
+    assert!(lines[0].starts_with("id  ptype"));
+    assert!(lines[1].ends_with("p      alice  data1  read"));
+
+    let output = db.run(&["list", "--json"]);
+    let rules: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(serde_json::json!(["bob", "alice"]), rules[1]["values"]);
+
+    let output = db.run(&["count", "--json"]);
+    assert_eq!(
+        serde_json::json!({ "g": 1, "p": 1 }),
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    );
+
+    let output = db.run(&["remove", "p", "alice", "data1", "write"]);
+    assert_eq!(Some(3), output.status.code());
+    assert_eq!("not found\n", stdout(&output));
+    assert_eq!(
+        Some(0),
+        db.run(&["remove", "p", "alice", "data1", "read"])
+            .status
+            .code()
+    );
+    assert_eq!(
+        Some(3),
+        db.run(&["remove-filtered", "g", "0", "carol"])
+            .status
+            .code()
+    );
+    assert_eq!(
+        Some(0),
+        db.run(&["remove-filtered", "g", "1", "alice"])
+            .status
+            .code()
+    );
+    assert_eq!("total    0\n", stdout(&db.run(&["count"])));
+}
+
+#[test]
+fn csv_round_trip() {
+    let db = Database::new("csv");
+    let file = db.0.with_extension("csv");
+    std::fs::write(&file, "p, alice, data1, read\ng, bob, alice\n").unwrap();
+
+    let output = db.run(&["import-csv", file.to_str().unwrap()]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("imported 2 rules, skipped 0\n", stdout(&output));
+    let output = db.run(&["import-csv", "--json", file.to_str().unwrap()]);
+    assert_eq!(
+        serde_json::json!({ "imported": { "g": 0, "p": 0 }, "skipped": 2 }),
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    );
+
+    let output = db.run(&["export-csv"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("g, bob, alice\np, alice, data1, read\n", stdout(&output));
+    std::fs::remove_file(file).unwrap();
+
+    let output = db.run(&["--table", "casbin_rule_other", "export-csv"]);
+    assert_eq!("", stdout(&output));
+}
+
+#[test]
+fn check_schema() {
+    let db = Database::new("schema");
+    // Any other command creates the table.
+    assert_eq!(Some(0), db.run(&["count"]).status.code());
+
+    let output = db.run(&["check-schema", "--json"]);
+    assert_eq!(Some(0), output.status.code());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(serde_json::json!(true), report["compatible"]);
+
+    let output = db.run(&["frobnicate"]);
+    assert_eq!(Some(2), output.status.code());
+}
+
+#[test]
+fn check_schema_incompatible() {
+    let db = Database::new("schema_incompatible");
+    db.execute(
+        "CREATE TABLE casbin_rule_legacy (
+            id INTEGER PRIMARY KEY,
+            ptype VARCHAR(12) NOT NULL,
+            v0 BLOB NOT NULL,
+            v1 VARCHAR(128) NOT NULL
+        )",
+    );
+
+    let output = db.run(&["--table", "casbin_rule_legacy", "check-schema", "--json"]);
+    assert_eq!(Some(4), output.status.code());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(serde_json::json!(false), report["compatible"]);
+    assert_eq!(serde_json::json!(["v0 (blob)"]), report["incompatible"]);
+    assert_eq!(
+        serde_json::json!(["v2", "v3", "v4", "v5"]),
+        report["missing"]
+    );
+
+    // A missing table is reported, not created.
+    for _ in 0..2 {
+        let output = db.run(&["--table", "casbin_rule_missing", "check-schema"]);
+        assert_eq!(Some(4), output.status.code());
+        assert!(stdout(&output).starts_with("compatible    false\n"));
+    }
+}